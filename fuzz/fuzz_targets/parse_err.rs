@@ -0,0 +1,19 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate simple_nats_client;
+
+use simple_nats_client::nats_client::PermissionViolation;
+use simple_nats_client::protocol::{Parser, ServerOp};
+
+// -ERR messages including permission violation correlation
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.feed(b"-ERR ");
+    parser.feed(data);
+    parser.feed(b"\r\n");
+    if let Ok(Some(ServerOp::Err(e))) = parser.next_op() {
+        let _ = e.is_retryable();
+        assert_eq!(e.permission_violation, PermissionViolation::parse(&e.error_message));
+    }
+});
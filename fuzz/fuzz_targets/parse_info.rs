@@ -0,0 +1,15 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate simple_nats_client;
+
+use simple_nats_client::protocol::Parser;
+
+// arbitrary JSON(or not) as INFO argument
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.feed(b"INFO ");
+    parser.feed(data);
+    parser.feed(b"\r\n");
+    let _ = parser.next_op();
+});
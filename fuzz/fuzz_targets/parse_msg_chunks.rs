@@ -0,0 +1,37 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate simple_nats_client;
+
+use simple_nats_client::protocol::Parser;
+
+fn parse_all(chunks: &[&[u8]]) -> Option<Vec<String>> {
+    let mut parser = Parser::new();
+    let mut ops = Vec::new();
+    for chunk in chunks {
+        parser.feed(chunk);
+        loop {
+            match parser.next_op() {
+                Ok(Some(op)) => ops.push(format!("{:?}", op)),
+                Ok(None) => break,
+                Err(_) => return None,
+            }
+        }
+    }
+    Some(ops)
+}
+
+// first byte decides chunk size, result must not depend on chunk boundaries
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let chunk_size = data[0] as usize + 1;
+    let body = &data[1..];
+    let whole = match parse_all(&[body]) {
+        Some(v) => v,
+        None => return,
+    };
+    let chunks: Vec<&[u8]> = body.chunks(chunk_size).collect();
+    assert_eq!(Some(whole), parse_all(&chunks));
+});
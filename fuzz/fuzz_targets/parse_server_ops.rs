@@ -0,0 +1,19 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate simple_nats_client;
+
+use simple_nats_client::protocol::Parser;
+
+// parser must not panic for any input
+fuzz_target!(|data: &[u8]| {
+    let mut parser = Parser::new();
+    parser.feed(data);
+    // errors discard invalid input, so parsing always progresses
+    for _ in 0..data.len() + 1 {
+        match parser.next_op() {
+            Ok(Some(_)) | Err(_) => {}
+            Ok(None) => break,
+        }
+    }
+});
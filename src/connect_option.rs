@@ -1,4 +1,32 @@
 
+use std::sync::Arc;
+
+use nats_client::{NatsError, NatsServerError};
+use server_info::ServerInfo;
+
+pub type DisconnectedCallback = Arc<dyn Fn(&str, &NatsError) + Send + Sync>;
+pub type ReconnectedCallback = Arc<dyn Fn(&str) + Send + Sync>;
+pub type ClosedCallback = Arc<dyn Fn() + Send + Sync>;
+pub type DiscoveredServersCallback = Arc<dyn Fn(&[String]) + Send + Sync>;
+pub type LameDuckCallback = Arc<dyn Fn(&ServerInfo) + Send + Sync>;
+pub type AsyncErrorCallback = Arc<dyn Fn(&NatsServerError, &[u64]) + Send + Sync>;
+
+/// connection event handlers registered by ConnectOption::set_*_handler
+#[derive(Clone, Default)]
+pub struct EventHandlers {
+    pub(crate) disconnected: Option<DisconnectedCallback>,
+    pub(crate) reconnected: Option<ReconnectedCallback>,
+    pub(crate) closed: Option<ClosedCallback>,
+    pub(crate) discovered_servers: Option<DiscoveredServersCallback>,
+    pub(crate) lame_duck: Option<LameDuckCallback>,
+    pub(crate) async_error: Option<AsyncErrorCallback>,
+}
+
+/// CONNECT parameters and client side settings
+///
+/// event handlers are not sent to server.
+/// they are called on the thread which calls NatsClient method(e.g. wait_message),
+/// after client state is updated, so handlers can not block other operations of the client.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConnectOption {
     pub verbose: bool,
@@ -10,6 +38,26 @@ pub struct ConnectOption {
     pub name: String,
     pub lang: String,
     pub version: String,
+    /// enable HPUB/HMSG, server ignores this if it does not support headers
+    #[serde(default = "default_true")]
+    pub headers: bool,
+    /// server replies status 503 to request if nobody subscribes its subject, requires headers
+    #[serde(default = "default_true")]
+    pub no_responders: bool,
+    /// receive messages published by this connection, false requires server proto >= 1
+    #[serde(default = "default_true")]
+    pub echo: bool,
+    /// protocol level, 1 enables asynchronous INFO(server discovery and lame duck mode)
+    #[serde(default = "default_protocol")]
+    pub protocol: i32,
+    #[serde(skip)]
+    handlers: EventHandlers,
+}
+
+impl Default for ConnectOption {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ConnectOption {
@@ -18,7 +66,7 @@ impl ConnectOption {
     }
     /// Create connection parameter with additional parameters
     ///
-    /// for suppress "+OK" message, pass verbose=true
+    /// server responds "+OK" to each operation if verbose=true, pass verbose=false to suppress it
     pub fn new_with_param(user: &str, pass: &str, verbose: bool, appname: &str) -> ConnectOption {
         Self::new_internal(Some(user), Some(pass), Some(verbose), Some(appname))
     }
@@ -33,7 +81,59 @@ impl ConnectOption {
             name: appname.unwrap_or("simple-rust-nats-client").to_owned(),
             lang: String::from("Rust"),
             version: String::from("0.0.1"),
+            headers: true,
+            no_responders: true,
+            echo: true,
+            protocol: default_protocol(),
+            handlers: EventHandlers::default(),
         }
     }
+    /// called when connection is lost, arguments are server("host:port") and the error
+    pub fn set_disconnected_handler<F>(&mut self, handler: F)
+        where F: Fn(&str, &NatsError) + Send + Sync + 'static
+    {
+        self.handlers.disconnected = Some(Arc::new(handler));
+    }
+    /// called when client connected to another server("host:port") after connection lost or lame duck mode
+    pub fn set_reconnected_handler<F>(&mut self, handler: F)
+        where F: Fn(&str) + Send + Sync + 'static
+    {
+        self.handlers.reconnected = Some(Arc::new(handler));
+    }
+    /// called when client is closed by close or drain, or reconnecting was failed
+    pub fn set_closed_handler<F>(&mut self, handler: F)
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.handlers.closed = Some(Arc::new(handler));
+    }
+    /// called when INFO notifies servers("host:port") which are not in the server pool
+    pub fn set_discovered_servers_handler<F>(&mut self, handler: F)
+        where F: Fn(&[String]) + Send + Sync + 'static
+    {
+        self.handlers.discovered_servers = Some(Arc::new(handler));
+    }
+    /// called when server enters lame duck mode
+    pub fn set_lame_duck_handler<F>(&mut self, handler: F)
+        where F: Fn(&ServerInfo) + Send + Sync + 'static
+    {
+        self.handlers.lame_duck = Some(Arc::new(handler));
+    }
+    /// called when server sends "-ERR", second argument is sids of affected subscriptions
+    pub fn set_async_error_handler<F>(&mut self, handler: F)
+        where F: Fn(&NatsServerError, &[u64]) + Send + Sync + 'static
+    {
+        self.handlers.async_error = Some(Arc::new(handler));
+    }
+    /// registered event handlers
+    pub fn handlers(&self) -> &EventHandlers {
+        &self.handlers
+    }
+}
+
+fn default_true() -> bool {
+    true
 }
 
+fn default_protocol() -> i32 {
+    1
+}
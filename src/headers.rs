@@ -0,0 +1,141 @@
+use std::str;
+
+use nats_client::NatsError;
+
+const VERSION_LINE: &str = "NATS/1.0";
+
+/// message headers which are sent by HPUB and received by HMSG
+///
+/// header names are case sensitive and same name can have multiple values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Headers {
+    status: Option<u16>,
+    description: Option<String>,
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Headers {
+        Headers::default()
+    }
+    /// set header value, existing values which have same name are removed
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.append(name, value);
+    }
+    /// add header value, existing values are kept
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries.push((name.to_owned(), value.to_owned()));
+    }
+    /// remove all values of name
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(n, _)| n != name);
+    }
+    /// get first value of name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+    /// get all values of name
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+    /// iterate (name, value) pairs in inserted order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// status code in version line(e.g. 503 for "no responders"), only set by server
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+    /// status description in version line, only set by server
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    /// check names and values can be written to header block
+    pub fn validate(&self) -> Result<(), NatsError> {
+        for (name, value) in &self.entries {
+            if name.is_empty() ||
+               name.bytes().any(|b| b == b':' || b <= b' ' || b == 0x7f) {
+                return Err(NatsError::InvalidHeader(name.clone()));
+            }
+            if value.bytes().any(|b| b == b'\r' || b == b'\n') {
+                return Err(NatsError::InvalidHeader(format!("{}: {}", name, value)));
+            }
+        }
+        Ok(())
+    }
+    /// serialize to header block, including trailing empty line
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret = String::from(VERSION_LINE);
+        ret.push_str("\r\n");
+        for (name, value) in &self.entries {
+            ret.push_str(name);
+            ret.push_str(": ");
+            ret.push_str(value);
+            ret.push_str("\r\n");
+        }
+        ret.push_str("\r\n");
+        ret.into_bytes()
+    }
+    /// byte length of to_bytes()
+    pub fn encoded_len(&self) -> usize {
+        let entries: usize = self.entries.iter().map(|(n, v)| n.len() + v.len() + 4).sum();
+        VERSION_LINE.len() + 2 + entries + 2
+    }
+    /// parse header block which is received by HMSG
+    pub fn from_bytes(data: &[u8]) -> Result<Headers, NatsError> {
+        let text = match str::from_utf8(data) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(NatsError::MessageParseError("headers".to_owned(), format!("{}", e)))
+            }
+        };
+        let mut lines = text.split("\r\n");
+        let version = lines.next().unwrap_or_default();
+        if !version.starts_with(VERSION_LINE) {
+            return Err(NatsError::MessageParseError("headers".to_owned(),
+                                                    format!("invalid version line:{}", version)));
+        }
+        let mut ret = Headers::new();
+        let status_line = version[VERSION_LINE.len()..].trim();
+        if !status_line.is_empty() {
+            let (code, description) = match status_line.find(' ') {
+                Some(i) => (&status_line[..i], status_line[i + 1..].trim()),
+                None => (status_line, ""),
+            };
+            ret.status = match code.parse::<u16>() {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    return Err(NatsError::MessageParseError(format!("headers status:{}", e),
+                                                            code.to_owned()))
+                }
+            };
+            if !description.is_empty() {
+                ret.description = Some(description.to_owned());
+            }
+        }
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+            match line.find(':') {
+                Some(i) => ret.append(line[..i].trim(), line[i + 1..].trim()),
+                None => {
+                    return Err(NatsError::MessageParseError("headers".to_owned(),
+                                                            format!("invalid header line:{}",
+                                                                    line)))
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
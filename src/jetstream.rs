@@ -0,0 +1,896 @@
+//! JetStream API(see: https://docs.nats.io/reference/reference-protocols/nats_api_reference)
+//!
+//! JetStream is a lightweight context which holds API prefix and timeout,
+//! NatsClient is passed to each operation.
+//!
+//! ```no_run
+//! extern crate simple_nats_client;
+//! use simple_nats_client::options::Options;
+//! use simple_nats_client::jetstream::{JetStream, StreamConfig};
+//! let mut c = Options::new().connect().unwrap();
+//! let js = JetStream::new();
+//! let mut config = StreamConfig::new("ORDERS");
+//! config.subjects = vec!["orders.>".to_owned()];
+//! let info = js.create_stream(&mut c, &config).unwrap();
+//! println!("{:?}", info.state);
+//! ```
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::mem;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use headers::Headers;
+use nats_client::{NatsClient, NatsError};
+
+pub mod consumer;
+pub mod kv;
+pub mod object_store;
+
+type Result<T> = ::std::result::Result<T, NatsError>;
+
+/// prefix of JetStream API subjects
+pub const DEFAULT_API_PREFIX: &str = "$JS.API";
+/// default timeout of API requests
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// id for deduplication in duplicate_window of stream
+pub const MSG_ID_HEADER: &str = "Nats-Msg-Id";
+/// publish fails unless subject belongs to the stream
+pub const EXPECTED_STREAM_HEADER: &str = "Nats-Expected-Stream";
+/// publish fails unless last sequence of stream is the value
+pub const EXPECTED_LAST_SEQUENCE_HEADER: &str = "Nats-Expected-Last-Sequence";
+/// publish fails unless last sequence of the subject is the value
+pub const EXPECTED_LAST_SUBJECT_SEQUENCE_HEADER: &str = "Nats-Expected-Last-Subject-Sequence";
+/// publish fails unless Nats-Msg-Id of last message is the value
+pub const EXPECTED_LAST_MSG_ID_HEADER: &str = "Nats-Expected-Last-Msg-Id";
+/// remove other messages of the subject("sub") or the stream("all") when message is stored
+pub const ROLLUP_HEADER: &str = "Nats-Rollup";
+/// stream of message returned by direct get
+pub const STREAM_HEADER: &str = "Nats-Stream";
+/// sequence of message returned by direct get
+pub const SEQUENCE_HEADER: &str = "Nats-Sequence";
+/// RFC3339 time when message returned by direct get was stored
+pub const TIME_STAMP_HEADER: &str = "Nats-Time-Stamp";
+/// subject of message returned by direct get
+pub const SUBJECT_HEADER: &str = "Nats-Subject";
+/// headers which direct get adds to stored message
+const DIRECT_GET_HEADERS: &[&str] = &[STREAM_HEADER,
+                                      SEQUENCE_HEADER,
+                                      TIME_STAMP_HEADER,
+                                      SUBJECT_HEADER,
+                                      "Nats-Last-Sequence",
+                                      "Nats-Num-Pending"];
+
+/// error returned by JetStream API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiError {
+    /// HTTP like status code
+    pub code: u16,
+    /// JetStream specific error code, see ApiError::*
+    #[serde(default)]
+    pub err_code: u64,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ApiError {
+    pub const CONSUMER_NOT_FOUND: u64 = 10014;
+    pub const NO_MESSAGE_FOUND: u64 = 10037;
+    pub const STREAM_NAME_EXIST: u64 = 10058;
+    pub const STREAM_NOT_FOUND: u64 = 10059;
+    pub const STREAM_WRONG_LAST_SEQUENCE: u64 = 10071;
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(code={}, err_code={})", self.description, self.code, self.err_code)
+    }
+}
+
+impl error::Error for ApiError {}
+
+/// how messages are removed from stream
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionPolicy {
+    /// kept until limits are reached
+    Limits,
+    /// kept while consumers exist and have not acknowledged
+    Interest,
+    /// removed when acknowledged by a consumer
+    WorkQueue,
+}
+
+/// what to do when stream reaches its limits
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscardPolicy {
+    /// remove old messages
+    Old,
+    /// reject new messages
+    New,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageType {
+    File,
+    Memory,
+}
+
+/// configuration of stream, -1 of limits means unlimited
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct StreamConfig {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub subjects: Vec<String>,
+    pub retention: RetentionPolicy,
+    pub max_consumers: i64,
+    pub max_msgs: i64,
+    pub max_bytes: i64,
+    /// zero means unlimited
+    #[serde(with = "nanos")]
+    pub max_age: Duration,
+    pub max_msgs_per_subject: i64,
+    pub max_msg_size: i64,
+    pub discard: DiscardPolicy,
+    pub storage: StorageType,
+    pub num_replicas: usize,
+    pub no_ack: bool,
+    /// window for Nats-Msg-Id deduplication, zero means server default
+    #[serde(with = "nanos")]
+    pub duplicate_window: Duration,
+    pub sealed: bool,
+    pub deny_delete: bool,
+    pub deny_purge: bool,
+    pub allow_rollup_hdrs: bool,
+    /// allow direct get($JS.API.DIRECT.GET)
+    pub allow_direct: bool,
+    pub mirror_direct: bool,
+    /// fields which are not defined above(e.g. mirror, sources), they are sent back on update
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            name: String::new(),
+            description: None,
+            subjects: Vec::new(),
+            retention: RetentionPolicy::Limits,
+            max_consumers: -1,
+            max_msgs: -1,
+            max_bytes: -1,
+            max_age: Duration::from_secs(0),
+            max_msgs_per_subject: -1,
+            max_msg_size: -1,
+            discard: DiscardPolicy::Old,
+            storage: StorageType::File,
+            num_replicas: 1,
+            no_ack: false,
+            duplicate_window: Duration::from_secs(0),
+            sealed: false,
+            deny_delete: false,
+            deny_purge: false,
+            allow_rollup_hdrs: false,
+            allow_direct: false,
+            mirror_direct: false,
+            extra: HashMap::new(),
+        }
+    }
+}
+
+impl StreamConfig {
+    /// configuration with default limits
+    pub fn new(name: &str) -> StreamConfig {
+        StreamConfig {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct StreamState {
+    pub messages: u64,
+    pub bytes: u64,
+    pub first_seq: u64,
+    /// RFC3339 timestamp
+    pub first_ts: String,
+    pub last_seq: u64,
+    pub last_ts: String,
+    pub consumer_count: usize,
+    pub num_subjects: u64,
+    pub num_deleted: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct StreamInfo {
+    pub config: StreamConfig,
+    /// RFC3339 timestamp
+    pub created: String,
+    pub state: StreamState,
+}
+
+/// parameters of purge_stream_with, all messages are removed if nothing is set
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PurgeRequest {
+    /// remove messages of the subject
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// remove messages whose sequence is less than seq
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// number of messages to keep
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PurgeResponse {
+    pub success: bool,
+    pub purged: u64,
+}
+
+/// limits of account or its tier, -1 means unlimited
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AccountLimits {
+    pub max_memory: i64,
+    pub max_storage: i64,
+    pub max_streams: i64,
+    pub max_consumers: i64,
+    pub max_ack_pending: i64,
+    pub memory_max_stream_bytes: i64,
+    pub storage_max_stream_bytes: i64,
+    /// max_bytes must be set to streams
+    pub max_bytes_required: bool,
+}
+
+impl Default for AccountLimits {
+    fn default() -> Self {
+        AccountLimits {
+            max_memory: -1,
+            max_storage: -1,
+            max_streams: -1,
+            max_consumers: -1,
+            max_ack_pending: -1,
+            memory_max_stream_bytes: -1,
+            storage_max_stream_bytes: -1,
+            max_bytes_required: false,
+        }
+    }
+}
+
+/// usage and limits of a tier(e.g. "R1", "R3")
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AccountTier {
+    /// bytes used by memory streams
+    pub memory: u64,
+    /// bytes used by file streams
+    pub storage: u64,
+    pub reserved_memory: u64,
+    pub reserved_storage: u64,
+    pub streams: u64,
+    pub consumers: u64,
+    pub limits: AccountLimits,
+}
+
+/// number of API calls of account
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ApiStats {
+    pub total: u64,
+    pub errors: u64,
+}
+
+/// usage and limits of JetStream account
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AccountInfo {
+    pub memory: u64,
+    pub storage: u64,
+    pub reserved_memory: u64,
+    pub reserved_storage: u64,
+    pub streams: u64,
+    pub consumers: u64,
+    pub limits: AccountLimits,
+    /// JetStream domain of the server
+    pub domain: Option<String>,
+    pub api: ApiStats,
+    /// usage per replication tier, empty if limits are not tiered
+    pub tiers: HashMap<String, AccountTier>,
+}
+
+/// acknowledgement of message stored in stream
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PubAck {
+    pub stream: String,
+    /// sequence of the message in stream
+    pub seq: u64,
+    /// true if the message was dropped as duplicate of Nats-Msg-Id
+    pub duplicate: bool,
+    pub domain: Option<String>,
+}
+
+/// deduplication and optimistic concurrency control of JetStream publish
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PublishOptions {
+    /// Nats-Msg-Id
+    pub msg_id: Option<String>,
+    /// Nats-Expected-Stream
+    pub expected_stream: Option<String>,
+    /// Nats-Expected-Last-Sequence
+    pub expected_last_sequence: Option<u64>,
+    /// Nats-Expected-Last-Subject-Sequence
+    pub expected_last_subject_sequence: Option<u64>,
+    /// Nats-Expected-Last-Msg-Id
+    pub expected_last_msg_id: Option<String>,
+    /// headers which are sent with above
+    pub headers: Headers,
+}
+
+impl PublishOptions {
+    /// headers of the publish, empty if nothing is set
+    pub fn to_headers(&self) -> Headers {
+        let mut ret = self.headers.clone();
+        if let Some(ref v) = self.msg_id {
+            ret.insert(MSG_ID_HEADER, v);
+        }
+        if let Some(ref v) = self.expected_stream {
+            ret.insert(EXPECTED_STREAM_HEADER, v);
+        }
+        if let Some(v) = self.expected_last_sequence {
+            ret.insert(EXPECTED_LAST_SEQUENCE_HEADER, &v.to_string());
+        }
+        if let Some(v) = self.expected_last_subject_sequence {
+            ret.insert(EXPECTED_LAST_SUBJECT_SEQUENCE_HEADER, &v.to_string());
+        }
+        if let Some(ref v) = self.expected_last_msg_id {
+            ret.insert(EXPECTED_LAST_MSG_ID_HEADER, v);
+        }
+        ret
+    }
+}
+
+/// message stored in stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub subject: String,
+    pub sequence: u64,
+    pub headers: Option<Headers>,
+    pub data: Vec<u8>,
+    /// when the message was stored
+    pub time: SystemTime,
+}
+
+/// STREAM.MSG.GET response, headers and data are base64 encoded
+#[derive(Deserialize)]
+struct RawStoredMessage {
+    subject: String,
+    seq: u64,
+    #[serde(default)]
+    hdrs: Option<String>,
+    #[serde(default)]
+    data: Option<String>,
+    time: String,
+}
+
+impl RawStoredMessage {
+    fn decode(self) -> Result<StoredMessage> {
+        let headers = match self.hdrs {
+            Some(ref v) if !v.is_empty() => Some(Headers::from_bytes(&decode_base64(v)?)?),
+            _ => None,
+        };
+        let data = match self.data {
+            Some(ref v) => decode_base64(v)?,
+            None => Vec::new(),
+        };
+        let time = match parse_time(&self.time) {
+            Some(v) => v,
+            None => {
+                return Err(NatsError::MessageParseError("jetstream response".to_owned(),
+                                                        format!("invalid time:{}", self.time)))
+            }
+        };
+        Ok(StoredMessage {
+            subject: self.subject,
+            sequence: self.seq,
+            headers,
+            data,
+            time,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct MsgGetResponse {
+    message: RawStoredMessage,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SuccessResponse {
+    success: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StreamNamesResponse {
+    total: usize,
+    streams: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StreamListResponse {
+    total: usize,
+    streams: Option<Vec<StreamInfo>>,
+}
+
+/// JetStream context
+#[derive(Debug, Clone)]
+pub struct JetStream {
+    prefix: String,
+    timeout: Duration,
+}
+
+impl Default for JetStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JetStream {
+    /// context with "$JS.API" prefix
+    pub fn new() -> JetStream {
+        JetStream {
+            prefix: DEFAULT_API_PREFIX.to_owned(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+    /// context with "$JS.<domain>.API" prefix, e.g. to use JetStream of leaf node or hub
+    pub fn with_domain(domain: &str) -> JetStream {
+        if domain.is_empty() {
+            return JetStream::new();
+        }
+        JetStream::with_api_prefix(&format!("$JS.{}.API", domain))
+    }
+    /// context with custom prefix, e.g. JetStream API imported from other account
+    pub fn with_api_prefix(prefix: &str) -> JetStream {
+        JetStream {
+            prefix: prefix.trim_end_matches('.').to_owned(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+    /// set timeout of API requests
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// prefix of API subjects(e.g. "$JS.API")
+    pub fn api_prefix(&self) -> &str {
+        &self.prefix
+    }
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    /// usage and limits of the account, e.g. to check quotas before writing
+    pub fn account_info(&self, c: &mut NatsClient) -> Result<AccountInfo> {
+        self.api_request(c, "INFO", b"")
+    }
+    /// create stream, error is returned if stream exists with different config
+    pub fn create_stream(&self, c: &mut NatsClient, config: &StreamConfig) -> Result<StreamInfo> {
+        validate_name(&config.name)?;
+        self.api_json(c, &format!("STREAM.CREATE.{}", config.name), config)
+    }
+    /// update config of existing stream
+    pub fn update_stream(&self, c: &mut NatsClient, config: &StreamConfig) -> Result<StreamInfo> {
+        validate_name(&config.name)?;
+        self.api_json(c, &format!("STREAM.UPDATE.{}", config.name), config)
+    }
+    /// delete stream and its messages
+    pub fn delete_stream(&self, c: &mut NatsClient, name: &str) -> Result<bool> {
+        validate_name(name)?;
+        let resp: SuccessResponse = self.api_request(c, &format!("STREAM.DELETE.{}", name), b"")?;
+        Ok(resp.success)
+    }
+    /// remove all messages of stream
+    pub fn purge_stream(&self, c: &mut NatsClient, name: &str) -> Result<PurgeResponse> {
+        validate_name(name)?;
+        self.api_request(c, &format!("STREAM.PURGE.{}", name), b"")
+    }
+    /// remove messages of stream selected by request
+    pub fn purge_stream_with(&self,
+                             c: &mut NatsClient,
+                             name: &str,
+                             request: &PurgeRequest)
+                             -> Result<PurgeResponse> {
+        validate_name(name)?;
+        self.api_json(c, &format!("STREAM.PURGE.{}", name), request)
+    }
+    pub fn stream_info(&self, c: &mut NatsClient, name: &str) -> Result<StreamInfo> {
+        validate_name(name)?;
+        self.api_request(c, &format!("STREAM.INFO.{}", name), b"")
+    }
+    /// names of all streams
+    pub fn stream_names(&self, c: &mut NatsClient) -> Result<Vec<String>> {
+        let mut ret = Vec::new();
+        loop {
+            let resp: StreamNamesResponse =
+                self.api_json(c, "STREAM.NAMES", &json!({ "offset": ret.len() }))?;
+            let names = resp.streams.unwrap_or_default();
+            if names.is_empty() {
+                break;
+            }
+            ret.extend(names);
+            if ret.len() >= resp.total {
+                break;
+            }
+        }
+        Ok(ret)
+    }
+    /// information of all streams
+    pub fn list_streams(&self, c: &mut NatsClient) -> Result<Vec<StreamInfo>> {
+        let mut ret = Vec::new();
+        loop {
+            let resp: StreamListResponse =
+                self.api_json(c, "STREAM.LIST", &json!({ "offset": ret.len() }))?;
+            let streams = resp.streams.unwrap_or_default();
+            if streams.is_empty() {
+                break;
+            }
+            ret.extend(streams);
+            if ret.len() >= resp.total {
+                break;
+            }
+        }
+        Ok(ret)
+    }
+    /// message of stream by sequence.
+    ///
+    /// NatsError::JetStream(ApiError::NO_MESSAGE_FOUND) is returned if it does not exist.
+    pub fn get_message(&self, c: &mut NatsClient, stream: &str, seq: u64) -> Result<StoredMessage> {
+        self.get_message_with(c, stream, &json!({ "seq": seq }))
+    }
+    /// last message of the subject in stream
+    pub fn get_last_message(&self, c: &mut NatsClient, stream: &str, subject: &str) -> Result<StoredMessage> {
+        self.get_message_with(c, stream, &json!({ "last_by_subj": subject }))
+    }
+    fn get_message_with(&self, c: &mut NatsClient, stream: &str, request: &Value) -> Result<StoredMessage> {
+        validate_name(stream)?;
+        let resp: MsgGetResponse = self.api_json(c, &format!("STREAM.MSG.GET.{}", stream), request)?;
+        resp.message.decode()
+    }
+    /// message of stream by sequence, read from any replica by "DIRECT.GET" API.
+    ///
+    /// falls back to get_message if allow_direct of the stream is not set.
+    pub fn direct_get_message(&self, c: &mut NatsClient, stream: &str, seq: u64) -> Result<StoredMessage> {
+        self.direct_get_with(c, stream, &json!({ "seq": seq }))
+    }
+    /// last message of the subject in stream, read from any replica by "DIRECT.GET" API.
+    ///
+    /// falls back to get_last_message if allow_direct of the stream is not set.
+    pub fn direct_get_last_message(&self, c: &mut NatsClient, stream: &str, subject: &str) -> Result<StoredMessage> {
+        self.direct_get_with(c, stream, &json!({ "last_by_subj": subject }))
+    }
+    fn direct_get_with(&self, c: &mut NatsClient, stream: &str, request: &Value) -> Result<StoredMessage> {
+        validate_name(stream)?;
+        let subject = format!("{}.DIRECT.GET.{}", self.prefix, stream);
+        let msg = match c.request(&subject, request.to_string().as_bytes(), self.timeout) {
+            // nobody subscribes DIRECT.GET unless allow_direct is set
+            Err(NatsError::NoResponders(_)) => return self.get_message_with(c, stream, request),
+            Err(e) => return Err(e),
+            Ok(v) => v,
+        };
+        match msg.status() {
+            None => {}
+            Some(404) => {
+                return Err(NatsError::JetStream(ApiError {
+                    code: 404,
+                    err_code: ApiError::NO_MESSAGE_FOUND,
+                    description: "no message found".to_owned(),
+                }))
+            }
+            Some(code) => return Err(consumer::status_error(&msg, code)),
+        }
+        let mut headers = msg.headers.unwrap_or_default();
+        let subject = headers.get(SUBJECT_HEADER).ok_or_else(|| missing_header(SUBJECT_HEADER))?.to_owned();
+        let sequence = headers.get(SEQUENCE_HEADER)
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| missing_header(SEQUENCE_HEADER))?;
+        let time = headers.get(TIME_STAMP_HEADER)
+            .and_then(parse_time)
+            .ok_or_else(|| missing_header(TIME_STAMP_HEADER))?;
+        for name in DIRECT_GET_HEADERS {
+            headers.remove(name);
+        }
+        Ok(StoredMessage {
+            subject,
+            sequence,
+            headers: if headers.is_empty() { None } else { Some(headers) },
+            data: msg.data,
+            time,
+        })
+    }
+    /// publish message to stream and wait for its acknowledgement
+    pub fn publish(&self, c: &mut NatsClient, subject: &str, data: &[u8]) -> Result<PubAck> {
+        self.publish_with_options(c, subject, data, &PublishOptions::default())
+    }
+    /// publish message with deduplication id, expectations and headers
+    ///
+    /// unmet expectation is returned as NatsError::JetStream(e.g. ApiError::STREAM_WRONG_LAST_SEQUENCE).
+    pub fn publish_with_options(&self,
+                                c: &mut NatsClient,
+                                subject: &str,
+                                data: &[u8],
+                                opts: &PublishOptions)
+                                -> Result<PubAck> {
+        let headers = opts.to_headers();
+        let msg = if headers.is_empty() {
+            c.request(subject, data, self.timeout)?
+        } else {
+            c.request_with_headers(subject, &headers, data, self.timeout)?
+        };
+        parse_response(&msg.data)
+    }
+    /// publisher which keeps at most max_in_flight publishes waiting for acknowledgement
+    pub fn async_publisher(&self, max_in_flight: usize) -> AsyncPublisher {
+        AsyncPublisher {
+            js: self.clone(),
+            max_in_flight: max_in_flight.max(1),
+            in_flight: HashMap::new(),
+            next_id: 0,
+            completed: Vec::new(),
+        }
+    }
+    /// request "<prefix>.<api>" with JSON body
+    pub(crate) fn api_json<T, R>(&self, c: &mut NatsClient, api: &str, body: &T) -> Result<R>
+        where T: Serialize,
+              R: DeserializeOwned
+    {
+        let body = serde_json::to_vec(body).map_err(NatsError::JsonError)?;
+        self.api_request(c, api, &body)
+    }
+    /// request "<prefix>.<api>" and parse its response
+    pub(crate) fn api_request<R: DeserializeOwned>(&self,
+                                                   c: &mut NatsClient,
+                                                   api: &str,
+                                                   body: &[u8])
+                                                   -> Result<R> {
+        let subject = format!("{}.{}", self.prefix, api);
+        let msg = c.request(&subject, body, self.timeout)?;
+        parse_response(&msg.data)
+    }
+}
+
+/// parse API response, "error" field is returned as NatsError::JetStream
+pub(crate) fn parse_response<R: DeserializeOwned>(data: &[u8]) -> Result<R> {
+    let value: Value = serde_json::from_slice(data).map_err(NatsError::JsonError)?;
+    if let Some(e) = value.get("error") {
+        let e: ApiError = serde_json::from_value(e.clone()).map_err(NatsError::JsonError)?;
+        return Err(NatsError::JetStream(e));
+    }
+    serde_json::from_value(value).map_err(NatsError::JsonError)
+}
+
+fn missing_header(name: &str) -> NatsError {
+    NatsError::MessageParseError("jetstream response".to_owned(), format!("{} is missing", name))
+}
+
+fn decode_base64(v: &str) -> Result<Vec<u8>> {
+    BASE64.decode(v).map_err(|e| NatsError::MessageParseError("jetstream response".to_owned(), e.to_string()))
+}
+
+/// parse RFC3339 time(e.g. "2024-01-01T00:00:00.123456789Z") which JetStream API uses
+pub(crate) fn parse_time(s: &str) -> Option<SystemTime> {
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || (b[10] != b'T' && b[10] != b't') || b[13] != b':' ||
+       b[16] != b':' {
+        return None;
+    }
+    let num = |from: usize, to: usize| s.get(from..to).and_then(|v| v.parse::<i64>().ok());
+    let (year, month, day) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+    let (hour, min, sec) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+    let mut rest = &s[19..];
+    let mut nanos = 0;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = frac.find(|c: char| !c.is_ascii_digit()).unwrap_or(frac.len());
+        if len == 0 {
+            return None;
+        }
+        nanos = format!("{:0<9}", &frac[..len.min(9)]).parse::<u32>().ok()?;
+        rest = &frac[len..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let v = rest.get(1..3)?.parse::<i64>().ok()? * 3600 + rest.get(4..6)?.parse::<i64>().ok()? * 60;
+            match rest.as_bytes()[0] {
+                b'+' => v,
+                b'-' => -v,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    // days from 1970-01-01 of the civil date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86_400 + hour * 3600 + min * 60 + sec - offset;
+    if secs < 0 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::new(secs as u64, nanos))
+}
+
+/// format time as RFC3339 in UTC(e.g. "2024-01-01T00:00:00.123456789Z")
+pub(crate) fn format_time(t: SystemTime) -> String {
+    let since = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs() as i64;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // civil date of days from 1970-01-01
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            year,
+            month,
+            day,
+            rem / 3600,
+            rem % 3600 / 60,
+            rem % 60,
+            since.subsec_nanos())
+}
+
+/// stream and consumer names are used as subject token
+pub(crate) fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(NatsError::InvalidSubject {
+            subject: name.to_owned(),
+            reason: "name must not be empty",
+        });
+    }
+    if name.contains(|c: char| c == '.' || c == '*' || c == '>' || c.is_whitespace()) {
+        return Err(NatsError::InvalidSubject {
+            subject: name.to_owned(),
+            reason: "name must not contain '.', '*', '>' or whitespace",
+        });
+    }
+    Ok(())
+}
+
+/// Duration as nanoseconds which JetStream API uses
+pub(crate) mod nanos {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(v: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(to_u64(*v))
+    }
+    pub fn to_u64(v: Duration) -> u64 {
+        v.as_secs() * 1_000_000_000 + u64::from(v.subsec_nanos())
+    }
+    pub fn is_zero(v: &Duration) -> bool {
+        *v == Duration::from_secs(0)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        // negative value means unset
+        let v = i64::deserialize(d)?;
+        Ok(Duration::from_nanos(v.max(0) as u64))
+    }
+}
+
+/// JetStream publisher which does not wait acknowledgement of each publish
+///
+/// acknowledgements are kept by the client until publish or flush receives them,
+/// so wait_message can be called between publishes. flush before dropping the publisher.
+///
+/// ```no_run
+/// extern crate simple_nats_client;
+/// use simple_nats_client::options::Options;
+/// use simple_nats_client::jetstream::{JetStream, PublishOptions};
+/// let mut c = Options::new().connect().unwrap();
+/// let mut publisher = JetStream::new().async_publisher(64);
+/// for i in 0..1000 {
+///     publisher.publish(&mut c, "orders.new", format!("{}", i).as_bytes(), &PublishOptions::default()).unwrap();
+/// }
+/// for (id, ack) in publisher.flush(&mut c).unwrap() {
+///     println!("{}: {:?}", id, ack);
+/// }
+/// ```
+pub struct AsyncPublisher {
+    js: JetStream,
+    max_in_flight: usize,
+    /// reply subject -> (sid of response inbox, id, subject)
+    in_flight: HashMap<String, (u64, u64, String)>,
+    next_id: u64,
+    completed: Vec<(u64, Result<PubAck>)>,
+}
+
+impl AsyncPublisher {
+    /// publish message, returns id which is passed with its acknowledgement.
+    ///
+    /// if max_in_flight publishes are not acknowledged, this waits for one of them.
+    pub fn publish(&mut self,
+                   c: &mut NatsClient,
+                   subject: &str,
+                   data: &[u8],
+                   opts: &PublishOptions)
+                   -> Result<u64> {
+        while self.in_flight.len() >= self.max_in_flight {
+            self.receive_ack(c)?;
+        }
+        let headers = opts.to_headers();
+        let headers = if headers.is_empty() { None } else { Some(&headers) };
+        let (sid, reply) = c.publish_request(subject, headers, data)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight.insert(reply, (sid, id, subject.to_owned()));
+        Ok(id)
+    }
+    /// number of publishes waiting for acknowledgement
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+    /// acknowledgements(or errors) received so far, in received order
+    pub fn take_completed(&mut self) -> Vec<(u64, Result<PubAck>)> {
+        mem::take(&mut self.completed)
+    }
+    /// wait for all acknowledgements and return completed ones.
+    ///
+    /// if an acknowledgement is not received within timeout of JetStream,
+    /// NatsError::Timeout is returned and publishes are kept in flight.
+    pub fn flush(&mut self, c: &mut NatsClient) -> Result<Vec<(u64, Result<PubAck>)>> {
+        while !self.in_flight.is_empty() {
+            self.receive_ack(c)?;
+        }
+        Ok(self.take_completed())
+    }
+    fn receive_ack(&mut self, c: &mut NatsClient) -> Result<()> {
+        let deadline = Instant::now() + self.js.timeout;
+        let msg = {
+            let in_flight = &self.in_flight;
+            c.wait_for_message(deadline, "AsyncPublisher::receive_ack", |msg| {
+                in_flight.get(&msg.subject).is_some_and(|v| v.0 == msg.sid)
+            })?
+        };
+        if let Some((_, id, subject)) = self.in_flight.remove(&msg.subject) {
+            let ack = if msg.is_no_responders() {
+                Err(NatsError::NoResponders(subject))
+            } else {
+                parse_response(&msg.data)
+            };
+            self.completed.push((id, ack));
+        }
+        Ok(())
+    }
+}
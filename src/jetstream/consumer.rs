@@ -0,0 +1,850 @@
+//! JetStream consumers
+//!
+//! ```no_run
+//! extern crate simple_nats_client;
+//! use std::time::Duration;
+//! use simple_nats_client::options::Options;
+//! use simple_nats_client::jetstream::JetStream;
+//! use simple_nats_client::jetstream::consumer::ConsumerConfig;
+//! let mut c = Options::new().connect().unwrap();
+//! let js = JetStream::new();
+//! js.create_consumer(&mut c, "ORDERS", &ConsumerConfig::durable("worker")).unwrap();
+//! let mut consumer = js.pull_consumer(&mut c, "ORDERS", "worker").unwrap();
+//! for msg in consumer.fetch(&mut c, 10, Duration::from_secs(1)).unwrap() {
+//!     println!("{:?}", msg.message.data);
+//!     msg.ack(&mut c).unwrap();
+//! }
+//! ```
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use super::{nanos, validate_name, ApiError, JetStream, Result, SuccessResponse};
+use nats_client::{NatsClient, NatsError, NatsMessage};
+
+/// status of idle heartbeat and flow control message
+pub const STATUS_CONTROL: u16 = 100;
+/// status of pull request which found no message
+pub const STATUS_NO_MESSAGES: u16 = 404;
+/// status of pull request which expired
+pub const STATUS_REQUEST_TIMEOUT: u16 = 408;
+/// status of pull request which was rejected or terminated(e.g. consumer was deleted)
+pub const STATUS_CONFLICT: u16 = 409;
+
+/// last consumer sequence delivered by server, set to idle heartbeat
+pub const LAST_CONSUMER_HEADER: &str = "Nats-Last-Consumer";
+/// last stream sequence delivered by server, set to idle heartbeat
+pub const LAST_STREAM_HEADER: &str = "Nats-Last-Stream";
+/// subject which stalled consumer waits for response of flow control, set to idle heartbeat
+pub const CONSUMER_STALLED_HEADER: &str = "Nats-Consumer-Stalled";
+
+/// heartbeat of pull request is not used if expires is shorter than this
+const MIN_HEARTBEAT_EXPIRES: Duration = Duration::from_secs(1);
+/// upper limit of heartbeat interval of pull request
+const MAX_PULL_HEARTBEAT: Duration = Duration::from_secs(5);
+/// wait for status of expired pull request after expires
+const PULL_EXPIRES_GRACE: Duration = Duration::from_secs(1);
+/// expires of pull request which next sends
+const NEXT_EXPIRES: Duration = Duration::from_secs(30);
+/// idle_heartbeat of ordered consumer if it is not configured
+const ORDERED_HEARTBEAT: Duration = Duration::from_secs(5);
+/// inactive_threshold of ordered consumer if it is not configured
+const ORDERED_INACTIVE_THRESHOLD: Duration = Duration::from_secs(300);
+
+/// where consumer starts delivery
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliverPolicy {
+    All,
+    Last,
+    New,
+    /// from opt_start_seq
+    ByStartSequence,
+    /// from opt_start_time
+    ByStartTime,
+    LastPerSubject,
+}
+
+/// how messages are acknowledged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AckPolicy {
+    None,
+    /// acknowledging a message acknowledges all preceding messages
+    All,
+    Explicit,
+}
+
+/// pace of delivery
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayPolicy {
+    Instant,
+    /// same pace as messages were stored
+    Original,
+}
+
+/// configuration of consumer, zero of limits means server default
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConsumerConfig {
+    /// consumer is kept without subscribers if this is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub durable_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub deliver_policy: DeliverPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opt_start_seq: Option<u64>,
+    /// RFC3339 time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opt_start_time: Option<String>,
+    pub ack_policy: AckPolicy,
+    /// message is redelivered if it is not acknowledged within this
+    #[serde(with = "nanos", skip_serializing_if = "nanos::is_zero")]
+    pub ack_wait: Duration,
+    /// -1 means unlimited
+    pub max_deliver: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_subject: Option<String>,
+    pub replay_policy: ReplayPolicy,
+    /// max pull requests waiting, only for pull consumer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_waiting: Option<i64>,
+    pub max_ack_pending: i64,
+    /// subject where messages are pushed, pull consumer if this is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deliver_subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deliver_group: Option<String>,
+    /// interval of heartbeat while no message is pushed, only for push consumer
+    #[serde(with = "nanos", skip_serializing_if = "nanos::is_zero")]
+    pub idle_heartbeat: Duration,
+    /// only for push consumer
+    pub flow_control: bool,
+    /// deliver only headers, size of payload is set to Nats-Msg-Size header
+    pub headers_only: bool,
+    /// ephemeral consumer is removed after this inactivity
+    #[serde(with = "nanos", skip_serializing_if = "nanos::is_zero")]
+    pub inactive_threshold: Duration,
+    /// zero means replicas of stream
+    pub num_replicas: usize,
+    pub mem_storage: bool,
+    /// fields which are not defined above, they are sent back on update
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Default for ConsumerConfig {
+    fn default() -> Self {
+        ConsumerConfig {
+            durable_name: None,
+            name: None,
+            description: None,
+            deliver_policy: DeliverPolicy::All,
+            opt_start_seq: None,
+            opt_start_time: None,
+            ack_policy: AckPolicy::Explicit,
+            ack_wait: Duration::from_secs(0),
+            max_deliver: -1,
+            filter_subject: None,
+            replay_policy: ReplayPolicy::Instant,
+            max_waiting: None,
+            max_ack_pending: 0,
+            deliver_subject: None,
+            deliver_group: None,
+            idle_heartbeat: Duration::from_secs(0),
+            flow_control: false,
+            headers_only: false,
+            inactive_threshold: Duration::from_secs(0),
+            num_replicas: 0,
+            mem_storage: false,
+            extra: HashMap::new(),
+        }
+    }
+}
+
+impl ConsumerConfig {
+    /// durable consumer with explicit ack
+    pub fn durable(name: &str) -> ConsumerConfig {
+        ConsumerConfig {
+            durable_name: Some(name.to_owned()),
+            ..Default::default()
+        }
+    }
+    /// name of durable or named consumer
+    pub fn consumer_name(&self) -> Option<&str> {
+        self.name.as_deref().or(self.durable_name.as_deref())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SequenceInfo {
+    pub consumer_seq: u64,
+    pub stream_seq: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_active: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ConsumerInfo {
+    pub stream_name: String,
+    pub name: String,
+    pub created: String,
+    pub config: ConsumerConfig,
+    /// last delivered sequences
+    pub delivered: SequenceInfo,
+    /// sequences below which all messages are acknowledged
+    pub ack_floor: SequenceInfo,
+    pub num_ack_pending: u64,
+    pub num_redelivered: u64,
+    /// pull requests waiting
+    pub num_waiting: u64,
+    /// messages which are not delivered yet
+    pub num_pending: u64,
+}
+
+impl JetStream {
+    /// create consumer of stream, ephemeral one is created if neither name nor durable_name is set.
+    ///
+    /// existing consumer is updated if config can be changed.
+    pub fn create_consumer(&self,
+                           c: &mut NatsClient,
+                           stream: &str,
+                           config: &ConsumerConfig)
+                           -> Result<ConsumerInfo> {
+        validate_name(stream)?;
+        let api = match config.consumer_name() {
+            Some(name) => {
+                validate_name(name)?;
+                format!("CONSUMER.CREATE.{}.{}", stream, name)
+            }
+            None => format!("CONSUMER.CREATE.{}", stream),
+        };
+        self.api_json(c, &api, &json!({ "stream_name": stream, "config": config }))
+    }
+    pub fn consumer_info(&self, c: &mut NatsClient, stream: &str, name: &str) -> Result<ConsumerInfo> {
+        validate_name(stream)?;
+        validate_name(name)?;
+        self.api_request(c, &format!("CONSUMER.INFO.{}.{}", stream, name), b"")
+    }
+    pub fn delete_consumer(&self, c: &mut NatsClient, stream: &str, name: &str) -> Result<bool> {
+        validate_name(stream)?;
+        validate_name(name)?;
+        let resp: SuccessResponse =
+            self.api_request(c, &format!("CONSUMER.DELETE.{}.{}", stream, name), b"")?;
+        Ok(resp.success)
+    }
+    /// look up existing pull consumer
+    pub fn pull_consumer(&self, c: &mut NatsClient, stream: &str, name: &str) -> Result<PullConsumer> {
+        let info = self.consumer_info(c, stream, name)?;
+        if info.config.deliver_subject.is_some() {
+            return Err(NatsError::ConsumerKindMismatch {
+                name: name.to_owned(),
+                push: true,
+            });
+        }
+        Ok(PullConsumer {
+            js: self.clone(),
+            stream: stream.to_owned(),
+            name: info.name.clone(),
+            info,
+            inbox: None,
+            pulls: 0,
+        })
+    }
+    /// subscribe deliver_subject and create push consumer.
+    ///
+    /// new inbox is used if deliver_subject is not set.
+    pub fn push_subscribe(&self,
+                          c: &mut NatsClient,
+                          stream: &str,
+                          config: &ConsumerConfig)
+                          -> Result<PushConsumer> {
+        let mut config = config.clone();
+        let deliver = match config.deliver_subject {
+            Some(ref v) => v.clone(),
+            None => c.new_inbox(),
+        };
+        config.deliver_subject = Some(deliver.clone());
+        // subscribe first not to lose messages delivered right after creation
+        let sid = c.subscribe(&deliver, config.deliver_group.as_deref())?;
+        match self.create_consumer(c, stream, &config) {
+            Ok(info) => Ok(PushConsumer::new(self, stream, info, sid)),
+            Err(e) => {
+                let _ = c.unsubscribe(sid);
+                Err(e)
+            }
+        }
+    }
+    /// look up existing push consumer and subscribe its deliver_subject
+    pub fn push_consumer(&self, c: &mut NatsClient, stream: &str, name: &str) -> Result<PushConsumer> {
+        let info = self.consumer_info(c, stream, name)?;
+        let sid = match info.config.deliver_subject {
+            Some(ref deliver) => c.subscribe(deliver, info.config.deliver_group.as_deref())?,
+            None => {
+                return Err(NatsError::ConsumerKindMismatch {
+                    name: name.to_owned(),
+                    push: false,
+                })
+            }
+        };
+        Ok(PushConsumer::new(self, stream, info, sid))
+    }
+    /// ephemeral push consumer without ack which delivers messages of stream in order.
+    ///
+    /// filter_subject, deliver_policy, opt_start_* and headers_only of config are used,
+    /// others are overwritten.
+    pub fn ordered_consumer(&self,
+                            c: &mut NatsClient,
+                            stream: &str,
+                            config: &ConsumerConfig)
+                            -> Result<OrderedConsumer> {
+        validate_name(stream)?;
+        let mut config = ConsumerConfig {
+            description: config.description.clone(),
+            deliver_policy: config.deliver_policy,
+            opt_start_seq: config.opt_start_seq,
+            opt_start_time: config.opt_start_time.clone(),
+            ack_policy: AckPolicy::None,
+            max_deliver: 1,
+            filter_subject: config.filter_subject.clone(),
+            replay_policy: config.replay_policy,
+            idle_heartbeat: config.idle_heartbeat,
+            flow_control: true,
+            headers_only: config.headers_only,
+            inactive_threshold: config.inactive_threshold,
+            num_replicas: 1,
+            mem_storage: true,
+            ..Default::default()
+        };
+        if nanos::is_zero(&config.idle_heartbeat) {
+            config.idle_heartbeat = ORDERED_HEARTBEAT;
+        }
+        if nanos::is_zero(&config.inactive_threshold) {
+            config.inactive_threshold = ORDERED_INACTIVE_THRESHOLD;
+        }
+        let mut consumer = OrderedConsumer {
+            js: self.clone(),
+            stream: stream.to_owned(),
+            config,
+            inner: None,
+            last_stream_sequence: 0,
+            resets: 0,
+        };
+        consumer.inner = Some(consumer.create(c)?);
+        Ok(consumer)
+    }
+}
+
+/// consumer which delivers messages on request.
+///
+/// inbox of pull requests is subscribed on first fetch, call unsubscribe when it is no longer used.
+pub struct PullConsumer {
+    js: JetStream,
+    stream: String,
+    name: String,
+    info: ConsumerInfo,
+    /// sid and prefix of "_INBOX.<random>.*"
+    inbox: Option<(u64, String)>,
+    pulls: u64,
+}
+
+impl PullConsumer {
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// information when consumer was looked up
+    pub fn cached_info(&self) -> &ConsumerInfo {
+        &self.info
+    }
+    /// look up current information
+    pub fn info(&mut self, c: &mut NatsClient) -> Result<&ConsumerInfo> {
+        self.info = self.js.consumer_info(c, &self.stream, &self.name)?;
+        Ok(&self.info)
+    }
+    /// request at most batch messages and wait for them until expires.
+    ///
+    /// messages received before expires are returned, so the result may be shorter than batch or empty.
+    /// if expires is 1 second or longer, the server sends idle heartbeats and
+    /// NatsError::MissedHeartbeat is returned when they stop before any message is received.
+    pub fn fetch(&mut self, c: &mut NatsClient, batch: usize, expires: Duration) -> Result<Vec<JetStreamMessage>> {
+        let heartbeat = if expires >= MIN_HEARTBEAT_EXPIRES {
+            Some((expires / 2).min(MAX_PULL_HEARTBEAT))
+        } else {
+            None
+        };
+        self.pull(c, batch, expires, heartbeat)
+    }
+    /// wait for next message, None is returned if no message is available within timeout.
+    ///
+    /// timeout is split into pull requests of 30 seconds.
+    pub fn next(&mut self, c: &mut NatsClient, timeout: Duration) -> Result<Option<JetStreamMessage>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let expires = (deadline - now).min(NEXT_EXPIRES);
+            if let Some(msg) = self.fetch(c, 1, expires)?.pop() {
+                return Ok(Some(msg));
+            }
+        }
+    }
+    /// unsubscribe inbox of pull requests, consumer itself is kept on server
+    pub fn unsubscribe(self, c: &mut NatsClient) -> Result<()> {
+        match self.inbox {
+            Some((sid, _)) => c.unsubscribe(sid),
+            None => Ok(()),
+        }
+    }
+    fn pull(&mut self,
+            c: &mut NatsClient,
+            batch: usize,
+            expires: Duration,
+            heartbeat: Option<Duration>)
+            -> Result<Vec<JetStreamMessage>> {
+        let (sid, prefix) = match self.inbox {
+            Some((sid, ref prefix)) => (sid, prefix.clone()),
+            None => {
+                let prefix = format!("{}.", c.new_inbox());
+                let sid = c.subscribe(&format!("{}*", prefix), None)?;
+                self.inbox = Some((sid, prefix.clone()));
+                (sid, prefix)
+            }
+        };
+        self.pulls += 1;
+        let reply = format!("{}{}", prefix, self.pulls);
+        let mut request = json!({ "batch": batch, "expires": nanos::to_u64(expires) });
+        if let Some(heartbeat) = heartbeat {
+            request["idle_heartbeat"] = json!(nanos::to_u64(heartbeat));
+        }
+        let subject = format!("{}.CONSUMER.MSG.NEXT.{}.{}", self.js.prefix, self.stream, self.name);
+        c.publish(&subject, Some(&reply), request.to_string().as_bytes())?;
+
+        let deadline = Instant::now() + expires + PULL_EXPIRES_GRACE;
+        let mut last_activity = Instant::now();
+        let mut ret = Vec::new();
+        while ret.len() < batch {
+            let wait_until = match heartbeat {
+                Some(heartbeat) => deadline.min(last_activity + heartbeat * 2),
+                None => deadline,
+            };
+            let msg = match c.wait_for_message(wait_until, "PullConsumer::fetch", |m| m.sid == sid) {
+                Ok(msg) => msg,
+                Err(NatsError::Timeout { .. }) if wait_until >= deadline || !ret.is_empty() => break,
+                Err(NatsError::Timeout { .. }) => {
+                    return Err(NatsError::MissedHeartbeat("PullConsumer::fetch".to_owned()))
+                }
+                Err(e) => return Err(e),
+            };
+            last_activity = Instant::now();
+            match msg.status() {
+                None => ret.push(JetStreamMessage::new(msg)),
+                Some(STATUS_CONTROL) => {}
+                // status of previous pull request
+                Some(_) if msg.subject != reply => {}
+                Some(STATUS_NO_MESSAGES) | Some(STATUS_REQUEST_TIMEOUT) => break,
+                Some(_) if !ret.is_empty() => break,
+                Some(503) => return Err(NatsError::NoResponders(subject)),
+                Some(code) => return Err(status_error(&msg, code)),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// subscription of consumer which pushes messages to deliver_subject.
+///
+/// flow control requests are answered while waiting for messages,
+/// NatsError::MissedHeartbeat is returned if idle_heartbeat is configured and two of them are missed.
+pub struct PushConsumer {
+    js: JetStream,
+    stream: String,
+    info: ConsumerInfo,
+    sid: u64,
+    ordered: bool,
+    last_stream_sequence: u64,
+    last_consumer_sequence: u64,
+}
+
+impl PushConsumer {
+    fn new(js: &JetStream, stream: &str, info: ConsumerInfo, sid: u64) -> PushConsumer {
+        PushConsumer {
+            js: js.clone(),
+            stream: stream.to_owned(),
+            info,
+            sid,
+            ordered: false,
+            last_stream_sequence: 0,
+            last_consumer_sequence: 0,
+        }
+    }
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+    /// sid of subscription to deliver_subject
+    pub fn sid(&self) -> u64 {
+        self.sid
+    }
+    /// information when consumer was created or looked up
+    pub fn cached_info(&self) -> &ConsumerInfo {
+        &self.info
+    }
+    /// look up current information
+    pub fn info(&mut self, c: &mut NatsClient) -> Result<&ConsumerInfo> {
+        self.info = self.js.consumer_info(c, &self.stream, &self.info.name)?;
+        Ok(&self.info)
+    }
+    /// check that consumer sequences are contiguous(default: false).
+    ///
+    /// NatsError::ConsumerSequenceMismatch is returned on a gap, which is also detected by idle heartbeat.
+    /// it is meaningful only for consumers which do not redeliver(AckPolicy::None).
+    pub fn set_ordered(&mut self, ordered: bool) {
+        self.ordered = ordered;
+    }
+    /// stream sequence of last delivered message
+    pub fn last_stream_sequence(&self) -> u64 {
+        self.last_stream_sequence
+    }
+    /// consumer sequence of last delivered message
+    pub fn last_consumer_sequence(&self) -> u64 {
+        self.last_consumer_sequence
+    }
+    /// wait for next message, None is returned if no message is pushed within timeout
+    pub fn next(&mut self, c: &mut NatsClient, timeout: Duration) -> Result<Option<JetStreamMessage>> {
+        let deadline = Instant::now() + timeout;
+        let heartbeat = self.info.config.idle_heartbeat;
+        let mut last_activity = Instant::now();
+        loop {
+            let wait_until = if nanos::is_zero(&heartbeat) {
+                deadline
+            } else {
+                deadline.min(last_activity + heartbeat * 2)
+            };
+            let sid = self.sid;
+            let msg = match c.wait_for_message(wait_until, "PushConsumer::next", |m| m.sid == sid) {
+                Ok(msg) => msg,
+                Err(NatsError::Timeout { .. }) if wait_until >= deadline => return Ok(None),
+                Err(NatsError::Timeout { .. }) => {
+                    return Err(NatsError::MissedHeartbeat("PushConsumer::next".to_owned()))
+                }
+                Err(e) => return Err(e),
+            };
+            last_activity = Instant::now();
+            if msg.status() == Some(STATUS_CONTROL) {
+                self.handle_control(c, &msg)?;
+                continue;
+            }
+            let msg = JetStreamMessage::new(msg);
+            let meta = msg.metadata()?;
+            if self.ordered && meta.consumer_sequence != self.last_consumer_sequence + 1 {
+                return Err(self.mismatch(meta.consumer_sequence));
+            }
+            self.last_stream_sequence = meta.stream_sequence;
+            self.last_consumer_sequence = meta.consumer_sequence;
+            return Ok(Some(msg));
+        }
+    }
+    /// unsubscribe deliver_subject, ephemeral consumer is removed by server after inactive_threshold
+    pub fn unsubscribe(self, c: &mut NatsClient) -> Result<()> {
+        c.unsubscribe(self.sid)
+    }
+    fn handle_control(&mut self, c: &mut NatsClient, msg: &NatsMessage) -> Result<()> {
+        // flow control request
+        if let Some(ref reply) = msg.reply {
+            c.publish(reply, None, b"")?;
+        }
+        let headers = match msg.headers {
+            Some(ref v) => v,
+            None => return Ok(()),
+        };
+        if let Some(stalled) = headers.get(CONSUMER_STALLED_HEADER) {
+            c.publish(stalled, None, b"")?;
+        }
+        if self.ordered {
+            let last = headers.get(LAST_CONSUMER_HEADER).and_then(|v| v.parse::<u64>().ok());
+            if let Some(last) = last {
+                if last != self.last_consumer_sequence {
+                    return Err(self.mismatch(last));
+                }
+            }
+        }
+        Ok(())
+    }
+    fn mismatch(&self, received: u64) -> NatsError {
+        NatsError::ConsumerSequenceMismatch {
+            stream_sequence: self.last_stream_sequence,
+            consumer_sequence: self.last_consumer_sequence,
+            received,
+        }
+    }
+}
+
+/// consumer for gap-free replay of stream.
+///
+/// when a gap of consumer sequence or missed heartbeat is detected,
+/// consumer is recreated from the stream sequence after the last delivered message.
+///
+/// ```no_run
+/// extern crate simple_nats_client;
+/// use std::time::Duration;
+/// use simple_nats_client::options::Options;
+/// use simple_nats_client::jetstream::JetStream;
+/// use simple_nats_client::jetstream::consumer::ConsumerConfig;
+/// let mut c = Options::new().connect().unwrap();
+/// let mut consumer = JetStream::new().ordered_consumer(&mut c, "ORDERS", &ConsumerConfig::default()).unwrap();
+/// // iterate until no message is delivered for a second
+/// for msg in consumer.messages(&mut c, Duration::from_secs(1)) {
+///     println!("{:?}", msg.unwrap().message.data);
+/// }
+/// ```
+pub struct OrderedConsumer {
+    js: JetStream,
+    stream: String,
+    config: ConsumerConfig,
+    inner: Option<PushConsumer>,
+    last_stream_sequence: u64,
+    resets: u64,
+}
+
+impl OrderedConsumer {
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+    /// stream sequence of last delivered message
+    pub fn last_stream_sequence(&self) -> u64 {
+        self.last_stream_sequence
+    }
+    /// number of times consumer was recreated
+    pub fn resets(&self) -> u64 {
+        self.resets
+    }
+    /// information when current consumer was created
+    pub fn cached_info(&self) -> Option<&ConsumerInfo> {
+        self.inner.as_ref().map(|v| v.cached_info())
+    }
+    /// wait for next message, None is returned if no message is delivered within timeout
+    pub fn next(&mut self, c: &mut NatsClient, timeout: Duration) -> Result<Option<JetStreamMessage>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut inner = match self.inner.take() {
+                Some(v) => v,
+                None => self.create(c)?,
+            };
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match inner.next(c, remaining) {
+                Ok(Some(msg)) => {
+                    self.last_stream_sequence = inner.last_stream_sequence();
+                    self.inner = Some(inner);
+                    return Ok(Some(msg));
+                }
+                Ok(None) => {
+                    self.inner = Some(inner);
+                    return Ok(None);
+                }
+                Err(NatsError::ConsumerSequenceMismatch { .. }) |
+                Err(NatsError::MissedHeartbeat(_)) => {
+                    debug!("recreate ordered consumer of {} from {}", self.stream, self.last_stream_sequence + 1);
+                    self.discard(c, inner);
+                    self.resets += 1;
+                }
+                Err(e) => {
+                    self.inner = Some(inner);
+                    return Err(e);
+                }
+            }
+        }
+    }
+    /// iterate messages until no message is delivered within timeout or an error is returned
+    pub fn messages<'a>(&'a mut self, c: &'a mut NatsClient, timeout: Duration) -> OrderedMessages<'a> {
+        OrderedMessages {
+            consumer: self,
+            client: c,
+            timeout,
+            done: false,
+        }
+    }
+    /// unsubscribe and delete consumer
+    pub fn close(mut self, c: &mut NatsClient) -> Result<()> {
+        match self.inner.take() {
+            Some(inner) => {
+                let name = inner.name().to_owned();
+                inner.unsubscribe(c)?;
+                self.js.delete_consumer(c, &self.stream, &name).map(|_| ())
+            }
+            None => Ok(()),
+        }
+    }
+    fn create(&self, c: &mut NatsClient) -> Result<PushConsumer> {
+        let mut config = self.config.clone();
+        if self.last_stream_sequence > 0 {
+            config.deliver_policy = DeliverPolicy::ByStartSequence;
+            config.opt_start_seq = Some(self.last_stream_sequence + 1);
+            config.opt_start_time = None;
+        }
+        let mut consumer = self.js.push_subscribe(c, &self.stream, &config)?;
+        consumer.set_ordered(true);
+        Ok(consumer)
+    }
+    /// remove old consumer, failure is ignored because server removes it after inactive_threshold
+    fn discard(&self, c: &mut NatsClient, inner: PushConsumer) {
+        let _ = c.drain_subscription(inner.sid(), self.js.timeout, |_| {});
+        let _ = self.js.delete_consumer(c, &self.stream, inner.name());
+    }
+}
+
+/// iterator of OrderedConsumer::messages
+pub struct OrderedMessages<'a> {
+    consumer: &'a mut OrderedConsumer,
+    client: &'a mut NatsClient,
+    timeout: Duration,
+    done: bool,
+}
+
+impl<'a> Iterator for OrderedMessages<'a> {
+    type Item = Result<JetStreamMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.consumer.next(self.client, self.timeout) {
+            Ok(Some(msg)) => Some(Ok(msg)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// status message which terminated request(e.g. 409 "Consumer Deleted")
+pub(crate) fn status_error(msg: &NatsMessage, code: u16) -> NatsError {
+    let description = msg.headers
+        .as_ref()
+        .and_then(|h| h.description())
+        .unwrap_or_default()
+        .to_owned();
+    NatsError::JetStream(ApiError {
+        code,
+        err_code: 0,
+        description,
+    })
+}
+
+/// delivery information in reply subject of JetStream message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageMetadata {
+    /// only set by servers which send the domain
+    pub domain: Option<String>,
+    pub stream: String,
+    pub consumer: String,
+    /// number of times the message was delivered
+    pub delivered: u64,
+    pub stream_sequence: u64,
+    pub consumer_sequence: u64,
+    /// when the message was stored
+    pub timestamp: SystemTime,
+    /// messages which are not delivered yet
+    pub pending: u64,
+}
+
+impl MessageMetadata {
+    /// parse "$JS.ACK.<stream>.<consumer>.<delivered>.<stream seq>.<consumer seq>.<timestamp>.<pending>"
+    ///
+    /// "$JS.ACK.<domain>.<account hash>.<stream>..." sent by newer servers is also parsed.
+    pub fn parse(reply: &str) -> Result<MessageMetadata> {
+        let tokens: Vec<&str> = reply.split('.').collect();
+        if tokens.len() < 9 || tokens[0] != "$JS" || tokens[1] != "ACK" {
+            return Err(metadata_error(reply));
+        }
+        let (domain, tokens) = match tokens.len() {
+            9 => (None, &tokens[2..]),
+            n if n >= 11 => {
+                let domain = if tokens[2] == "_" { None } else { Some(tokens[2].to_owned()) };
+                (domain, &tokens[4..11])
+            }
+            _ => return Err(metadata_error(reply)),
+        };
+        let number = |i: usize| tokens[i].parse::<u64>().map_err(|_| metadata_error(reply));
+        Ok(MessageMetadata {
+            domain,
+            stream: tokens[0].to_owned(),
+            consumer: tokens[1].to_owned(),
+            delivered: number(2)?,
+            stream_sequence: number(3)?,
+            consumer_sequence: number(4)?,
+            timestamp: UNIX_EPOCH + Duration::from_nanos(number(5)?),
+            pending: number(6)?,
+        })
+    }
+}
+
+fn metadata_error(reply: &str) -> NatsError {
+    NatsError::MessageParseError("invalid JetStream ack subject".to_owned(), reply.to_owned())
+}
+
+/// message delivered by consumer, it is acknowledged to its reply subject
+#[derive(Debug)]
+pub struct JetStreamMessage {
+    pub message: NatsMessage,
+}
+
+impl JetStreamMessage {
+    pub fn new(message: NatsMessage) -> JetStreamMessage {
+        JetStreamMessage { message }
+    }
+    /// delivery information parsed from reply subject
+    pub fn metadata(&self) -> Result<MessageMetadata> {
+        MessageMetadata::parse(self.ack_subject()?)
+    }
+    /// message was processed
+    pub fn ack(&self, c: &mut NatsClient) -> Result<()> {
+        self.reply(c, b"+ACK")
+    }
+    /// message was not processed, it is redelivered immediately
+    pub fn nak(&self, c: &mut NatsClient) -> Result<()> {
+        self.reply(c, b"-NAK")
+    }
+    /// message was not processed, it is redelivered after delay
+    pub fn nak_with_delay(&self, c: &mut NatsClient, delay: Duration) -> Result<()> {
+        let body = format!("-NAK {}", json!({ "delay": nanos::to_u64(delay) }));
+        self.reply(c, body.as_bytes())
+    }
+    /// message is still being processed, ack_wait is reset
+    pub fn in_progress(&self, c: &mut NatsClient) -> Result<()> {
+        self.reply(c, b"+WPI")
+    }
+    /// message is never redelivered
+    pub fn term(&self, c: &mut NatsClient) -> Result<()> {
+        self.reply(c, b"+TERM")
+    }
+    fn ack_subject(&self) -> Result<&str> {
+        match self.message.reply {
+            Some(ref reply) => Ok(reply),
+            None => Err(NatsError::NotAckable(self.message.subject.clone())),
+        }
+    }
+    fn reply(&self, c: &mut NatsClient, body: &[u8]) -> Result<()> {
+        let subject = self.ack_subject()?.to_owned();
+        c.publish(&subject, None, body).map(|_| ())
+    }
+}
+
@@ -0,0 +1,397 @@
+//! Key-Value store on JetStream
+//!
+//! bucket is stream "KV_<bucket>" and key is subject "$KV.<bucket>.<key>".
+//!
+//! ```no_run
+//! extern crate simple_nats_client;
+//! use std::time::Duration;
+//! use simple_nats_client::options::Options;
+//! use simple_nats_client::jetstream::JetStream;
+//! use simple_nats_client::jetstream::kv::KvConfig;
+//! let mut c = Options::new().connect().unwrap();
+//! let kv = JetStream::new().create_key_value(&mut c, &KvConfig::new("config")).unwrap();
+//! let revision = kv.put(&mut c, "app.timeout", b"10").unwrap();
+//! kv.update(&mut c, "app.timeout", b"20", revision).unwrap();
+//! let entry = kv.get(&mut c, "app.timeout").unwrap().unwrap();
+//! assert_eq!(b"20".to_vec(), entry.value);
+//! let mut watcher = kv.watch(&mut c, "app.>").unwrap();
+//! while let Some(entry) = watcher.next(&mut c, Duration::from_secs(10)).unwrap() {
+//!     println!("{} {:?}", entry.key, entry.operation);
+//! }
+//! ```
+use std::time::{Duration, SystemTime};
+
+use headers::Headers;
+use nats_client::{NatsClient, NatsError};
+use super::consumer::{ConsumerConfig, DeliverPolicy, JetStreamMessage, OrderedConsumer};
+use super::{ApiError, DiscardPolicy, JetStream, PublishOptions, Result, StorageType, StoredMessage,
+            StreamConfig, ROLLUP_HEADER};
+
+/// operation of entry("DEL" or "PURGE"), not set for put
+pub const KV_OPERATION_HEADER: &str = "KV-Operation";
+/// max number of revisions kept for each key
+pub const MAX_HISTORY: i64 = 64;
+
+/// duplicate_window of bucket stream if ttl is not shorter
+const DEFAULT_DUPLICATE_WINDOW: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Put,
+    /// value was deleted, older revisions are kept
+    Delete,
+    /// value and older revisions were removed
+    Purge,
+}
+
+impl Operation {
+    fn from_headers(headers: Option<&Headers>) -> Operation {
+        match headers.and_then(|h| h.get(KV_OPERATION_HEADER)) {
+            Some("DEL") => Operation::Delete,
+            Some("PURGE") => Operation::Purge,
+            _ => Operation::Put,
+        }
+    }
+}
+
+/// configuration of bucket, -1 of limits means unlimited
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvConfig {
+    pub bucket: String,
+    pub description: Option<String>,
+    /// number of revisions kept for each key(1 to MAX_HISTORY)
+    pub history: i64,
+    /// entries are removed after this, zero means forever
+    pub ttl: Duration,
+    pub max_value_size: i64,
+    pub max_bytes: i64,
+    pub storage: StorageType,
+    pub num_replicas: usize,
+}
+
+impl KvConfig {
+    /// file storage bucket which keeps the latest value of each key
+    pub fn new(bucket: &str) -> KvConfig {
+        KvConfig {
+            bucket: bucket.to_owned(),
+            description: None,
+            history: 1,
+            ttl: Duration::from_secs(0),
+            max_value_size: -1,
+            max_bytes: -1,
+            storage: StorageType::File,
+            num_replicas: 1,
+        }
+    }
+    fn to_stream_config(&self) -> StreamConfig {
+        let mut config = StreamConfig::new(&stream_name(&self.bucket));
+        config.description = self.description.clone();
+        config.subjects = vec![format!("$KV.{}.>", self.bucket)];
+        config.max_msgs_per_subject = self.history;
+        config.max_age = self.ttl;
+        config.max_msg_size = self.max_value_size;
+        config.max_bytes = self.max_bytes;
+        config.storage = self.storage;
+        config.num_replicas = self.num_replicas;
+        config.discard = DiscardPolicy::New;
+        config.duplicate_window = if self.ttl > Duration::from_secs(0) && self.ttl < DEFAULT_DUPLICATE_WINDOW {
+            self.ttl
+        } else {
+            DEFAULT_DUPLICATE_WINDOW
+        };
+        config.allow_rollup_hdrs = true;
+        config.deny_delete = true;
+        config.allow_direct = true;
+        config
+    }
+}
+
+/// revision of key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub bucket: String,
+    pub key: String,
+    /// empty for delete and purge
+    pub value: Vec<u8>,
+    /// stream sequence of the entry
+    pub revision: u64,
+    pub created: SystemTime,
+    /// number of entries after this when it was delivered, zero for get
+    pub delta: u64,
+    pub operation: Operation,
+}
+
+impl JetStream {
+    /// create bucket, existing one is returned if config is same
+    pub fn create_key_value(&self, c: &mut NatsClient, config: &KvConfig) -> Result<Store> {
+        validate_bucket(&config.bucket)?;
+        if config.history < 1 || config.history > MAX_HISTORY {
+            return Err(NatsError::InvalidConfig {
+                name: "history",
+                value: config.history.to_string(),
+                reason: "history must be between 1 and 64",
+            });
+        }
+        self.create_stream(c, &config.to_stream_config())?;
+        Ok(Store::new(self, &config.bucket))
+    }
+    /// bind existing bucket
+    pub fn key_value(&self, c: &mut NatsClient, bucket: &str) -> Result<Store> {
+        validate_bucket(bucket)?;
+        self.stream_info(c, &stream_name(bucket))?;
+        Ok(Store::new(self, bucket))
+    }
+    /// delete bucket and its entries
+    pub fn delete_key_value(&self, c: &mut NatsClient, bucket: &str) -> Result<bool> {
+        validate_bucket(bucket)?;
+        self.delete_stream(c, &stream_name(bucket))
+    }
+}
+
+/// bucket of Key-Value store
+#[derive(Debug, Clone)]
+pub struct Store {
+    js: JetStream,
+    bucket: String,
+    stream: String,
+    /// "$KV.<bucket>."
+    prefix: String,
+}
+
+impl Store {
+    fn new(js: &JetStream, bucket: &str) -> Store {
+        Store {
+            js: js.clone(),
+            bucket: bucket.to_owned(),
+            stream: stream_name(bucket),
+            prefix: format!("$KV.{}.", bucket),
+        }
+    }
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+    /// name of stream which stores entries
+    pub fn stream_name(&self) -> &str {
+        &self.stream
+    }
+    /// latest value of key, None if it does not exist or was deleted
+    pub fn get(&self, c: &mut NatsClient, key: &str) -> Result<Option<Entry>> {
+        Ok(self.entry(c, key)?.and_then(|e| if e.operation == Operation::Put { Some(e) } else { None }))
+    }
+    /// latest entry of key including delete and purge
+    pub fn entry(&self, c: &mut NatsClient, key: &str) -> Result<Option<Entry>> {
+        validate_key(key, false)?;
+        match self.js.get_last_message(c, &self.stream, &self.subject(key)) {
+            Ok(msg) => Ok(Some(self.stored_entry(msg))),
+            Err(NatsError::JetStream(ref e)) if e.err_code == ApiError::NO_MESSAGE_FOUND => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    /// set value, returns its revision
+    pub fn put(&self, c: &mut NatsClient, key: &str, value: &[u8]) -> Result<u64> {
+        validate_key(key, false)?;
+        Ok(self.js.publish(c, &self.subject(key), value)?.seq)
+    }
+    /// set value only if key does not exist or was deleted
+    pub fn create(&self, c: &mut NatsClient, key: &str, value: &[u8]) -> Result<u64> {
+        let err = match self.update(c, key, value, 0) {
+            Err(NatsError::JetStream(e)) => e,
+            other => return other,
+        };
+        if err.err_code == ApiError::STREAM_WRONG_LAST_SEQUENCE {
+            if let Some(entry) = self.entry(c, key)? {
+                if entry.operation != Operation::Put {
+                    return self.update(c, key, value, entry.revision);
+                }
+            }
+        }
+        Err(NatsError::JetStream(err))
+    }
+    /// set value only if latest revision of key is the revision.
+    ///
+    /// NatsError::JetStream(ApiError::STREAM_WRONG_LAST_SEQUENCE) is returned otherwise.
+    pub fn update(&self, c: &mut NatsClient, key: &str, value: &[u8], revision: u64) -> Result<u64> {
+        validate_key(key, false)?;
+        let opts = PublishOptions {
+            expected_last_subject_sequence: Some(revision),
+            ..Default::default()
+        };
+        Ok(self.js.publish_with_options(c, &self.subject(key), value, &opts)?.seq)
+    }
+    /// put delete marker, older revisions are kept
+    pub fn delete(&self, c: &mut NatsClient, key: &str) -> Result<u64> {
+        let mut headers = Headers::new();
+        headers.insert(KV_OPERATION_HEADER, "DEL");
+        self.put_marker(c, key, headers)
+    }
+    /// put purge marker and remove older revisions
+    pub fn purge(&self, c: &mut NatsClient, key: &str) -> Result<u64> {
+        let mut headers = Headers::new();
+        headers.insert(KV_OPERATION_HEADER, "PURGE");
+        headers.insert(ROLLUP_HEADER, "sub");
+        self.put_marker(c, key, headers)
+    }
+    /// all revisions of key which are kept, in revision order
+    pub fn history(&self, c: &mut NatsClient, key: &str) -> Result<Vec<Entry>> {
+        validate_key(key, false)?;
+        let mut ret = Vec::new();
+        self.collect(c, key, DeliverPolicy::All, false, |e| ret.push(e))?;
+        Ok(ret)
+    }
+    /// keys whose latest entry is not deleted
+    pub fn keys(&self, c: &mut NatsClient) -> Result<Vec<String>> {
+        let mut ret = Vec::new();
+        self.collect(c, ">", DeliverPolicy::LastPerSubject, true, |e| {
+            if e.operation == Operation::Put {
+                ret.push(e.key);
+            }
+        })?;
+        Ok(ret)
+    }
+    /// watch keys which match the pattern(e.g. "app.*", ">").
+    ///
+    /// latest entry of each key is delivered first, and then updates.
+    pub fn watch(&self, c: &mut NatsClient, key_pattern: &str) -> Result<Watcher> {
+        validate_key(key_pattern, true)?;
+        let consumer = self.ordered_consumer(c, key_pattern, DeliverPolicy::LastPerSubject, false)?;
+        Ok(Watcher {
+            store: self.clone(),
+            consumer,
+        })
+    }
+    /// watch all keys
+    pub fn watch_all(&self, c: &mut NatsClient) -> Result<Watcher> {
+        self.watch(c, ">")
+    }
+    fn subject(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+    fn put_marker(&self, c: &mut NatsClient, key: &str, headers: Headers) -> Result<u64> {
+        validate_key(key, false)?;
+        let opts = PublishOptions {
+            headers,
+            ..Default::default()
+        };
+        Ok(self.js.publish_with_options(c, &self.subject(key), b"", &opts)?.seq)
+    }
+    fn ordered_consumer(&self,
+                        c: &mut NatsClient,
+                        key_pattern: &str,
+                        deliver_policy: DeliverPolicy,
+                        headers_only: bool)
+                        -> Result<OrderedConsumer> {
+        let config = ConsumerConfig {
+            deliver_policy,
+            filter_subject: Some(self.subject(key_pattern)),
+            headers_only,
+            ..Default::default()
+        };
+        self.js.ordered_consumer(c, &self.stream, &config)
+    }
+    /// pass entries which exist now to f
+    fn collect<F>(&self,
+                  c: &mut NatsClient,
+                  key_pattern: &str,
+                  deliver_policy: DeliverPolicy,
+                  headers_only: bool,
+                  mut f: F)
+                  -> Result<()>
+        where F: FnMut(Entry)
+    {
+        let mut consumer = self.ordered_consumer(c, key_pattern, deliver_policy, headers_only)?;
+        let ret = self.receive_entries(c, &mut consumer, &mut f);
+        // ephemeral consumer is deleted even if receiving failed
+        let closed = consumer.close(c);
+        ret.and(closed)
+    }
+    fn receive_entries<F>(&self, c: &mut NatsClient, consumer: &mut OrderedConsumer, f: &mut F) -> Result<()>
+        where F: FnMut(Entry)
+    {
+        let mut pending = consumer.cached_info().map_or(0, |info| info.num_pending);
+        while pending > 0 {
+            let msg = match consumer.next(c, self.js.timeout)? {
+                Some(v) => v,
+                None => return Err(NatsError::timeout("Store::collect")),
+            };
+            let entry = self.message_entry(msg)?;
+            pending = entry.delta;
+            f(entry);
+        }
+        Ok(())
+    }
+    fn stored_entry(&self, msg: StoredMessage) -> Entry {
+        Entry {
+            bucket: self.bucket.clone(),
+            key: msg.subject[self.prefix.len().min(msg.subject.len())..].to_owned(),
+            operation: Operation::from_headers(msg.headers.as_ref()),
+            value: msg.data,
+            revision: msg.sequence,
+            created: msg.time,
+            delta: 0,
+        }
+    }
+    fn message_entry(&self, msg: JetStreamMessage) -> Result<Entry> {
+        let meta = msg.metadata()?;
+        let msg = msg.message;
+        Ok(Entry {
+            bucket: self.bucket.clone(),
+            key: msg.subject[self.prefix.len().min(msg.subject.len())..].to_owned(),
+            operation: Operation::from_headers(msg.headers.as_ref()),
+            value: msg.data,
+            revision: meta.stream_sequence,
+            created: meta.timestamp,
+            delta: meta.pending,
+        })
+    }
+}
+
+/// updates of keys, see Store::watch
+pub struct Watcher {
+    store: Store,
+    consumer: OrderedConsumer,
+}
+
+impl Watcher {
+    /// wait for next entry, None is returned if nothing is updated within timeout
+    pub fn next(&mut self, c: &mut NatsClient, timeout: Duration) -> Result<Option<Entry>> {
+        match self.consumer.next(c, timeout)? {
+            Some(msg) => self.store.message_entry(msg).map(Some),
+            None => Ok(None),
+        }
+    }
+    /// stop watching
+    pub fn stop(self, c: &mut NatsClient) -> Result<()> {
+        self.consumer.close(c)
+    }
+}
+
+fn stream_name(bucket: &str) -> String {
+    format!("KV_{}", bucket)
+}
+
+pub(crate) fn validate_bucket(bucket: &str) -> Result<()> {
+    if bucket.is_empty() || !bucket.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(NatsError::InvalidConfig {
+            name: "bucket",
+            value: bucket.to_owned(),
+            reason: "bucket name must consist of alphanumeric, '_' and '-'",
+        });
+    }
+    Ok(())
+}
+
+/// key is subject tokens of alphanumeric, '-', '_', '/' and '=', wildcards are allowed for watch
+fn validate_key(key: &str, allow_wildcard: bool) -> Result<()> {
+    let valid_char = |c: char| {
+        c.is_ascii_alphanumeric() || "-_/=.".contains(c) || (allow_wildcard && (c == '*' || c == '>'))
+    };
+    if key.is_empty() || key.starts_with('.') || key.ends_with('.') || !key.chars().all(valid_char) ||
+       ::subject::validate_subject(key).is_err() {
+        return Err(NatsError::InvalidConfig {
+            name: "key",
+            value: key.to_owned(),
+            reason: "key must consist of alphanumeric, '-', '_', '/', '=' and '.' separated tokens",
+        });
+    }
+    Ok(())
+}
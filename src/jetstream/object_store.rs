@@ -0,0 +1,696 @@
+//! Object store on JetStream
+//!
+//! bucket is stream "OBJ_<bucket>", data of object is split into chunks of "$O.<bucket>.C.<nuid>"
+//! and its metadata is stored to "$O.<bucket>.M.<base64url of name>".
+//!
+//! ```no_run
+//! extern crate simple_nats_client;
+//! use std::fs::File;
+//! use std::io;
+//! use simple_nats_client::options::Options;
+//! use simple_nats_client::jetstream::JetStream;
+//! use simple_nats_client::jetstream::object_store::{ObjectMeta, ObjectStoreConfig};
+//! let mut c = Options::new().connect().unwrap();
+//! let store = JetStream::new().create_object_store(&mut c, &ObjectStoreConfig::new("artifacts")).unwrap();
+//! let info = store.put(&mut c, &ObjectMeta::new("app.tar.gz"), &mut File::open("app.tar.gz").unwrap()).unwrap();
+//! println!("{} bytes in {} chunks", info.size, info.chunks);
+//! let mut reader = store.get(&mut c, "app.tar.gz").unwrap().unwrap();
+//! io::copy(&mut reader, &mut File::create("copy.tar.gz").unwrap()).unwrap();
+//! ```
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem;
+use std::time::{Duration, SystemTime};
+
+use base64::engine::general_purpose::URL_SAFE as BASE64_URL;
+use base64::Engine;
+use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+
+use headers::Headers;
+use nats_client::{unique_token, NatsClient, NatsError};
+use super::consumer::{ConsumerConfig, DeliverPolicy, JetStreamMessage, OrderedConsumer};
+use super::kv::validate_bucket;
+use super::{format_time, ApiError, AsyncPublisher, DiscardPolicy, JetStream, PublishOptions, PurgeRequest,
+            Result, StorageType, StreamConfig, ROLLUP_HEADER};
+
+/// size of chunk if it is not set to ObjectMeta
+pub const DEFAULT_CHUNK_SIZE: usize = 128 * 1024;
+/// prefix of ObjectInfo::digest
+pub const DIGEST_PREFIX: &str = "SHA-256=";
+
+/// chunks which are published without waiting for acknowledgement
+const MAX_CHUNKS_IN_FLIGHT: usize = 32;
+
+/// configuration of bucket, -1 of limits means unlimited
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectStoreConfig {
+    pub bucket: String,
+    pub description: Option<String>,
+    /// objects are removed after this, zero means forever
+    pub ttl: Duration,
+    pub max_bytes: i64,
+    pub storage: StorageType,
+    pub num_replicas: usize,
+}
+
+impl ObjectStoreConfig {
+    /// file storage bucket without limits
+    pub fn new(bucket: &str) -> ObjectStoreConfig {
+        ObjectStoreConfig {
+            bucket: bucket.to_owned(),
+            description: None,
+            ttl: Duration::from_secs(0),
+            max_bytes: -1,
+            storage: StorageType::File,
+            num_replicas: 1,
+        }
+    }
+    fn to_stream_config(&self) -> StreamConfig {
+        let mut config = StreamConfig::new(&stream_name(&self.bucket));
+        config.description = self.description.clone();
+        config.subjects = vec![format!("$O.{}.C.>", self.bucket), format!("$O.{}.M.>", self.bucket)];
+        config.max_age = self.ttl;
+        config.max_bytes = self.max_bytes;
+        config.storage = self.storage;
+        config.num_replicas = self.num_replicas;
+        config.discard = DiscardPolicy::New;
+        config.allow_rollup_hdrs = true;
+        config.allow_direct = true;
+        config
+    }
+}
+
+/// target of link, whole bucket if name is not set
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ObjectLink {
+    pub bucket: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ObjectOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<ObjectLink>,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub max_chunk_size: u64,
+}
+
+/// metadata of object
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ObjectInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<ObjectOptions>,
+    pub bucket: String,
+    /// unique id of chunks
+    pub nuid: String,
+    pub size: u64,
+    /// RFC3339 timestamp
+    pub mtime: String,
+    pub chunks: u64,
+    /// "SHA-256=<base64url of digest>"
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub digest: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub deleted: bool,
+    /// fields which are not defined above(e.g. headers)
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl ObjectInfo {
+    /// target if this is link
+    pub fn link(&self) -> Option<&ObjectLink> {
+        self.options.as_ref().and_then(|o| o.link.as_ref())
+    }
+    pub fn is_link(&self) -> bool {
+        self.link().is_some()
+    }
+}
+
+/// metadata which is passed to put
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectMeta {
+    pub name: String,
+    pub description: Option<String>,
+    /// zero means DEFAULT_CHUNK_SIZE, chunk is not larger than max_payload of server
+    pub chunk_size: usize,
+}
+
+impl ObjectMeta {
+    pub fn new(name: &str) -> ObjectMeta {
+        ObjectMeta {
+            name: name.to_owned(),
+            description: None,
+            chunk_size: 0,
+        }
+    }
+}
+
+impl JetStream {
+    /// create bucket, existing one is returned if config is same
+    pub fn create_object_store(&self, c: &mut NatsClient, config: &ObjectStoreConfig) -> Result<ObjectStore> {
+        validate_bucket(&config.bucket)?;
+        self.create_stream(c, &config.to_stream_config())?;
+        Ok(ObjectStore::new(self, &config.bucket))
+    }
+    /// bind existing bucket
+    pub fn object_store(&self, c: &mut NatsClient, bucket: &str) -> Result<ObjectStore> {
+        validate_bucket(bucket)?;
+        self.stream_info(c, &stream_name(bucket))?;
+        Ok(ObjectStore::new(self, bucket))
+    }
+    /// delete bucket and its objects
+    pub fn delete_object_store(&self, c: &mut NatsClient, bucket: &str) -> Result<bool> {
+        validate_bucket(bucket)?;
+        self.delete_stream(c, &stream_name(bucket))
+    }
+}
+
+/// bucket of object store
+#[derive(Debug, Clone)]
+pub struct ObjectStore {
+    js: JetStream,
+    bucket: String,
+    stream: String,
+}
+
+impl ObjectStore {
+    fn new(js: &JetStream, bucket: &str) -> ObjectStore {
+        ObjectStore {
+            js: js.clone(),
+            bucket: bucket.to_owned(),
+            stream: stream_name(bucket),
+        }
+    }
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+    /// name of stream which stores objects
+    pub fn stream_name(&self) -> &str {
+        &self.stream
+    }
+    /// metadata of object, None if it does not exist or was deleted
+    pub fn info(&self, c: &mut NatsClient, name: &str) -> Result<Option<ObjectInfo>> {
+        Ok(self.meta(c, name)?.and_then(|v| if v.deleted { None } else { Some(v) }))
+    }
+    /// writer which stores object when it is finished, existing object is replaced
+    pub fn writer<'a>(&self, c: &'a mut NatsClient, meta: &ObjectMeta) -> Result<ObjectWriter<'a>> {
+        validate_name(&meta.name)?;
+        let mut chunk_size = if meta.chunk_size == 0 { DEFAULT_CHUNK_SIZE } else { meta.chunk_size };
+        let max_payload = c.get_server_info().max_payload;
+        if max_payload > 0 {
+            chunk_size = chunk_size.min(max_payload as usize);
+        }
+        let info = ObjectInfo {
+            name: meta.name.clone(),
+            description: meta.description.clone(),
+            options: Some(ObjectOptions {
+                link: None,
+                max_chunk_size: chunk_size as u64,
+            }),
+            bucket: self.bucket.clone(),
+            nuid: unique_token(),
+            ..Default::default()
+        };
+        Ok(ObjectWriter {
+            client: c,
+            store: self.clone(),
+            publisher: self.js.async_publisher(MAX_CHUNKS_IN_FLIGHT),
+            info,
+            chunk_size,
+            buf: Vec::new(),
+            hasher: Sha256::new(),
+            done: false,
+        })
+    }
+    /// store all data of reader as object
+    pub fn put<R: Read>(&self, c: &mut NatsClient, meta: &ObjectMeta, reader: &mut R) -> Result<ObjectInfo> {
+        let mut writer = self.writer(c, meta)?;
+        if let Err(e) = io::copy(reader, &mut writer) {
+            let _ = writer.abort();
+            return Err(from_io_error(e, "ObjectStore::put"));
+        }
+        writer.finish()
+    }
+    pub fn put_bytes(&self, c: &mut NatsClient, name: &str, data: &[u8]) -> Result<ObjectInfo> {
+        self.put(c, &ObjectMeta::new(name), &mut io::Cursor::new(data))
+    }
+    /// reader of object, None if it does not exist or was deleted.
+    ///
+    /// link to object is followed, and digest is verified when all data is read.
+    pub fn get<'a>(&self, c: &'a mut NatsClient, name: &str) -> Result<Option<ObjectReader<'a>>> {
+        let mut info = match self.info(c, name)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if let Some(link) = info.link().cloned() {
+            let target = match link.name {
+                Some(ref v) => v,
+                None => {
+                    return Err(NatsError::InvalidLink {
+                        name: name.to_owned(),
+                        reason: "object is link to bucket",
+                    })
+                }
+            };
+            info = match ObjectStore::new(&self.js, &link.bucket).info(c, target)? {
+                Some(ref v) if v.is_link() => {
+                    return Err(NatsError::InvalidLink {
+                        name: name.to_owned(),
+                        reason: "link to link is not allowed",
+                    })
+                }
+                Some(v) => v,
+                None => return Ok(None),
+            };
+        }
+        let consumer = if info.chunks > 0 {
+            let config = ConsumerConfig {
+                filter_subject: Some(chunk_subject(&info.bucket, &info.nuid)),
+                ..Default::default()
+            };
+            Some(self.js.ordered_consumer(c, &stream_name(&info.bucket), &config)?)
+        } else {
+            None
+        };
+        Ok(Some(ObjectReader {
+            client: c,
+            timeout: self.js.timeout,
+            info,
+            consumer,
+            buf: Vec::new(),
+            pos: 0,
+            received_chunks: 0,
+            received_size: 0,
+            hasher: Sha256::new(),
+            verified: false,
+        }))
+    }
+    /// all data of object, None if it does not exist or was deleted
+    pub fn get_bytes(&self, c: &mut NatsClient, name: &str) -> Result<Option<Vec<u8>>> {
+        let mut reader = match self.get(c, name)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let mut ret = Vec::with_capacity(reader.info().size as usize);
+        while reader.next_chunk()? {
+            ret.extend_from_slice(&reader.buf);
+        }
+        Ok(Some(ret))
+    }
+    /// mark object as deleted and remove its chunks, false if it does not exist
+    pub fn delete(&self, c: &mut NatsClient, name: &str) -> Result<bool> {
+        let mut info = match self.info(c, name)? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+        let nuid = mem::take(&mut info.nuid);
+        info.deleted = true;
+        info.size = 0;
+        info.chunks = 0;
+        info.digest.clear();
+        info.mtime = format_time(SystemTime::now());
+        self.publish_meta(c, &info)?;
+        if !nuid.is_empty() && !info.is_link() {
+            self.purge_chunks(c, &nuid)?;
+        }
+        Ok(true)
+    }
+    /// add link to the object, existing link of the name is replaced
+    pub fn add_link(&self, c: &mut NatsClient, name: &str, target: &ObjectInfo) -> Result<ObjectInfo> {
+        if target.deleted || target.is_link() {
+            return Err(NatsError::InvalidLink {
+                name: target.name.clone(),
+                reason: "link target must be existing object",
+            });
+        }
+        self.put_link(c,
+                      name,
+                      ObjectLink {
+                          bucket: target.bucket.clone(),
+                          name: Some(target.name.clone()),
+                      })
+    }
+    /// add link to the bucket
+    pub fn add_bucket_link(&self, c: &mut NatsClient, name: &str, bucket: &str) -> Result<ObjectInfo> {
+        validate_bucket(bucket)?;
+        self.put_link(c,
+                      name,
+                      ObjectLink {
+                          bucket: bucket.to_owned(),
+                          name: None,
+                      })
+    }
+    /// metadata of objects which are not deleted
+    pub fn list(&self, c: &mut NatsClient) -> Result<Vec<ObjectInfo>> {
+        let mut consumer = self.meta_consumer(c)?;
+        let ret = self.receive_infos(c, &mut consumer);
+        // ephemeral consumer is deleted even if receiving failed
+        let closed = consumer.close(c);
+        ret.and_then(|v| closed.map(|_| v))
+    }
+    fn receive_infos(&self, c: &mut NatsClient, consumer: &mut OrderedConsumer) -> Result<Vec<ObjectInfo>> {
+        let mut pending = consumer.cached_info().map_or(0, |info| info.num_pending);
+        let mut ret = Vec::new();
+        while pending > 0 {
+            let msg = match consumer.next(c, self.js.timeout)? {
+                Some(v) => v,
+                None => return Err(NatsError::timeout("ObjectStore::list")),
+            };
+            pending = msg.metadata()?.pending;
+            let info = decode_info(&msg)?;
+            if !info.deleted {
+                ret.push(info);
+            }
+        }
+        Ok(ret)
+    }
+    /// watch changes of objects, metadata of each object is delivered first
+    pub fn watch(&self, c: &mut NatsClient) -> Result<ObjectWatcher> {
+        Ok(ObjectWatcher { consumer: self.meta_consumer(c)? })
+    }
+    /// metadata including deleted one
+    fn meta(&self, c: &mut NatsClient, name: &str) -> Result<Option<ObjectInfo>> {
+        validate_name(name)?;
+        let msg = match self.js.get_last_message(c, &self.stream, &self.meta_subject(name)) {
+            Ok(v) => v,
+            Err(NatsError::JetStream(ref e)) if e.err_code == ApiError::NO_MESSAGE_FOUND => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut info: ObjectInfo = serde_json::from_slice(&msg.data).map_err(NatsError::JsonError)?;
+        if info.mtime.is_empty() {
+            info.mtime = format_time(msg.time);
+        }
+        Ok(Some(info))
+    }
+    fn put_link(&self, c: &mut NatsClient, name: &str, link: ObjectLink) -> Result<ObjectInfo> {
+        if let Some(old) = self.info(c, name)? {
+            if !old.is_link() {
+                return Err(NatsError::ObjectExists(name.to_owned()));
+            }
+        }
+        let info = ObjectInfo {
+            name: name.to_owned(),
+            options: Some(ObjectOptions {
+                link: Some(link),
+                max_chunk_size: 0,
+            }),
+            bucket: self.bucket.clone(),
+            nuid: unique_token(),
+            mtime: format_time(SystemTime::now()),
+            ..Default::default()
+        };
+        self.publish_meta(c, &info)?;
+        Ok(info)
+    }
+    fn publish_meta(&self, c: &mut NatsClient, info: &ObjectInfo) -> Result<()> {
+        let data = serde_json::to_vec(info).map_err(NatsError::JsonError)?;
+        let mut headers = Headers::new();
+        headers.insert(ROLLUP_HEADER, "sub");
+        let opts = PublishOptions {
+            headers,
+            ..Default::default()
+        };
+        self.js.publish_with_options(c, &self.meta_subject(&info.name), &data, &opts).map(|_| ())
+    }
+    fn purge_chunks(&self, c: &mut NatsClient, nuid: &str) -> Result<()> {
+        let request = PurgeRequest {
+            filter: Some(chunk_subject(&self.bucket, nuid)),
+            ..Default::default()
+        };
+        self.js.purge_stream_with(c, &self.stream, &request).map(|_| ())
+    }
+    fn meta_consumer(&self, c: &mut NatsClient) -> Result<OrderedConsumer> {
+        let config = ConsumerConfig {
+            deliver_policy: DeliverPolicy::LastPerSubject,
+            filter_subject: Some(format!("$O.{}.M.>", self.bucket)),
+            ..Default::default()
+        };
+        self.js.ordered_consumer(c, &self.stream, &config)
+    }
+    fn meta_subject(&self, name: &str) -> String {
+        format!("$O.{}.M.{}", self.bucket, BASE64_URL.encode(name))
+    }
+}
+
+/// writer of object, see ObjectStore::writer.
+///
+/// data is published as chunks while writing and the object is stored by finish.
+/// chunks are removed if finish fails or writer is dropped without finish.
+pub struct ObjectWriter<'a> {
+    client: &'a mut NatsClient,
+    store: ObjectStore,
+    publisher: AsyncPublisher,
+    info: ObjectInfo,
+    chunk_size: usize,
+    buf: Vec<u8>,
+    hasher: Sha256,
+    /// metadata is published or chunks are removed
+    done: bool,
+}
+
+impl<'a> ObjectWriter<'a> {
+    /// publish rest of data and metadata, chunks of replaced object are removed
+    pub fn finish(mut self) -> Result<ObjectInfo> {
+        if !self.buf.is_empty() {
+            let chunk = mem::take(&mut self.buf);
+            self.publish_chunk(&chunk)?;
+        }
+        for (_, ack) in self.publisher.flush(self.client)? {
+            ack?;
+        }
+        let digest = mem::take(&mut self.hasher).finalize();
+        self.info.digest = format!("{}{}", DIGEST_PREFIX, BASE64_URL.encode(digest));
+        self.info.mtime = format_time(SystemTime::now());
+        let old = self.store.meta(self.client, &self.info.name)?;
+        self.store.publish_meta(self.client, &self.info)?;
+        self.done = true;
+        if let Some(old) = old {
+            if !old.nuid.is_empty() && old.nuid != self.info.nuid && !old.is_link() {
+                self.store.purge_chunks(self.client, &old.nuid)?;
+            }
+        }
+        Ok(mem::take(&mut self.info))
+    }
+    /// remove chunks which were published
+    pub fn abort(mut self) -> Result<()> {
+        self.remove_chunks()
+    }
+    fn remove_chunks(&mut self) -> Result<()> {
+        self.done = true;
+        let _ = self.publisher.flush(self.client);
+        self.store.purge_chunks(self.client, &self.info.nuid)
+    }
+    fn publish_chunk(&mut self, chunk: &[u8]) -> Result<()> {
+        self.hasher.update(chunk);
+        self.info.size += chunk.len() as u64;
+        self.info.chunks += 1;
+        let subject = chunk_subject(&self.info.bucket, &self.info.nuid);
+        self.publisher.publish(self.client, &subject, chunk, &PublishOptions::default())?;
+        for (_, ack) in self.publisher.take_completed() {
+            ack?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for ObjectWriter<'a> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        if let Err(e) = self.remove_chunks() {
+            warn!("failed to remove chunks of {}: {}", self.info.name, e);
+        }
+    }
+}
+
+impl<'a> Write for ObjectWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        while self.buf.len() >= self.chunk_size {
+            let rest = self.buf.split_off(self.chunk_size);
+            let chunk = mem::replace(&mut self.buf, rest);
+            self.publish_chunk(&chunk).map_err(to_io_error)?;
+        }
+        Ok(data.len())
+    }
+    /// chunks are published by write and finish
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// reader of object, see ObjectStore::get
+pub struct ObjectReader<'a> {
+    client: &'a mut NatsClient,
+    timeout: Duration,
+    info: ObjectInfo,
+    consumer: Option<OrderedConsumer>,
+    buf: Vec<u8>,
+    pos: usize,
+    received_chunks: u64,
+    received_size: u64,
+    hasher: Sha256,
+    verified: bool,
+}
+
+impl<'a> ObjectReader<'a> {
+    /// metadata of the object, target of link is returned for link
+    pub fn info(&self) -> &ObjectInfo {
+        &self.info
+    }
+    /// receive next chunk to buf, false if all chunks are received
+    fn next_chunk(&mut self) -> Result<bool> {
+        if self.received_chunks >= self.info.chunks {
+            if let Some(consumer) = self.consumer.take() {
+                consumer.close(self.client)?;
+            }
+            if !self.verified {
+                self.verified = true;
+                self.verify()?;
+            }
+            return Ok(false);
+        }
+        let msg = match self.consumer {
+            Some(ref mut consumer) => consumer.next(self.client, self.timeout)?,
+            None => return Ok(false),
+        };
+        let data = match msg {
+            Some(v) => v.message.data,
+            None => return Err(NatsError::timeout("ObjectReader::read")),
+        };
+        self.hasher.update(&data);
+        self.received_chunks += 1;
+        self.received_size += data.len() as u64;
+        self.buf = data;
+        self.pos = 0;
+        Ok(true)
+    }
+    fn verify(&mut self) -> Result<()> {
+        if self.received_size != self.info.size {
+            return Err(NatsError::MessageParseError("object size".to_owned(),
+                                                    format!("{} != {}", self.received_size, self.info.size)));
+        }
+        let digest = format!("{}{}", DIGEST_PREFIX, BASE64_URL.encode(mem::take(&mut self.hasher).finalize()));
+        if !self.info.digest.is_empty() && digest != self.info.digest {
+            return Err(NatsError::MessageParseError("object digest".to_owned(),
+                                                    format!("{} != {}", digest, self.info.digest)));
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for ObjectReader<'a> {
+    fn drop(&mut self) {
+        // consumer is left if reader is dropped before all chunks are read
+        if let Some(consumer) = self.consumer.take() {
+            if let Err(e) = consumer.close(self.client) {
+                warn!("failed to close consumer of {}: {}", self.info.name, e);
+            }
+        }
+    }
+}
+
+impl<'a> Read for ObjectReader<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.buf.len() {
+            if !self.next_chunk().map_err(to_io_error)? {
+                return Ok(0);
+            }
+        }
+        let len = out.len().min(self.buf.len() - self.pos);
+        out[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// changes of objects, see ObjectStore::watch
+pub struct ObjectWatcher {
+    consumer: OrderedConsumer,
+}
+
+impl ObjectWatcher {
+    /// wait for next change including deletion, None is returned if nothing is changed within timeout
+    pub fn next(&mut self, c: &mut NatsClient, timeout: Duration) -> Result<Option<ObjectInfo>> {
+        match self.consumer.next(c, timeout)? {
+            Some(msg) => decode_info(&msg).map(Some),
+            None => Ok(None),
+        }
+    }
+    /// stop watching
+    pub fn stop(self, c: &mut NatsClient) -> Result<()> {
+        self.consumer.close(c)
+    }
+}
+
+fn stream_name(bucket: &str) -> String {
+    format!("OBJ_{}", bucket)
+}
+
+fn chunk_subject(bucket: &str, nuid: &str) -> String {
+    format!("$O.{}.C.{}", bucket, nuid)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(NatsError::InvalidConfig {
+            name: "object name",
+            value: name.to_owned(),
+            reason: "object name must not be empty",
+        });
+    }
+    Ok(())
+}
+
+fn decode_info(msg: &JetStreamMessage) -> Result<ObjectInfo> {
+    let mut info: ObjectInfo = msg.message.json()?;
+    if info.mtime.is_empty() {
+        info.mtime = format_time(msg.metadata()?.timestamp);
+    }
+    Ok(info)
+}
+
+fn to_io_error(e: NatsError) -> io::Error {
+    match e {
+        NatsError::ConnectionError { ref error, .. } => io::Error::new(error.kind(), e),
+        NatsError::Timeout { .. } => io::Error::new(ErrorKind::TimedOut, e),
+        NatsError::MessageParseError(..) | NatsError::JsonError(_) => io::Error::new(ErrorKind::InvalidData, e),
+        _ => io::Error::other(e),
+    }
+}
+
+/// NatsError wrapped by to_io_error is returned as it is
+fn from_io_error(e: io::Error, operation: &str) -> NatsError {
+    let e = if e.get_ref().is_some_and(|v| v.is::<NatsError>()) {
+        let kind = e.kind();
+        match e.into_inner().map(|v| v.downcast::<NatsError>()) {
+            Some(Ok(inner)) => return *inner,
+            Some(Err(inner)) => io::Error::new(kind, inner),
+            None => io::Error::from(kind),
+        }
+    } else {
+        e
+    };
+    NatsError::Io {
+        error: e,
+        operation: operation.to_owned(),
+    }
+}
+
+fn is_zero(v: &u64) -> bool {
+    *v == 0
+}
+
+fn is_false(v: &bool) -> bool {
+    !*v
+}
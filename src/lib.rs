@@ -1,4 +1,7 @@
+extern crate base64;
 extern crate serde;
+extern crate sha2;
+#[macro_use]
 extern crate serde_json;
 
 #[macro_use]
@@ -10,3 +13,8 @@ extern crate log;
 pub mod server_info;
 pub mod nats_client;
 pub mod connect_option;
+pub mod headers;
+pub mod subject;
+pub mod protocol;
+pub mod options;
+pub mod jetstream;
@@ -5,20 +5,48 @@ use std::net::TcpStream;
 use std::io::Read;
 use std::io::Write;
 use std::io::Error;
+use std::io::ErrorKind;
+use std::error;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::string::FromUtf8Error;
 use std::string::String;
-use std::str::FromStr;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::process;
 use std::fmt;
+use std::mem;
 use std::result;
 use std::time;
-use std::cmp;
 use std::net;
+use std::net::ToSocketAddrs;
+use std::thread;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use protocol;
+use protocol::{Parser, ServerOp};
+use options::{Options, ReconnectPolicy};
 
 type Result<T> = result::Result<T, NatsError>;
+
+/// time to wait messages from lame duck server after moving to another server,
+/// if neither connect timeout nor read timeout is set
+const LAME_DUCK_DRAIN_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+type AsyncErrorHandler = Box<dyn FnMut(&NatsServerError, &[u64]) + Send>;
+type LameDuckHandler = Box<dyn FnMut(&ServerInfo) + Send>;
+type DisconnectedHandler = Box<dyn FnMut(&str, &NatsError) + Send>;
+type ReconnectedHandler = Box<dyn FnMut(&str) + Send>;
+type ClosedHandler = Box<dyn FnMut() + Send>;
+type DiscoveredServersHandler = Box<dyn FnMut(&[String]) + Send>;
 pub type ServerInfo = ::server_info::ServerInfo;
 pub type ConnectOption = ::connect_option::ConnectOption;
+pub type Headers = ::headers::Headers;
+pub type JetStreamApiError = ::jetstream::ApiError;
 
 /// NATS publish/subscribe client
 ///
@@ -26,11 +54,115 @@ pub type ConnectOption = ::connect_option::ConnectOption;
 /// 
 /// this is not threadsafe because of having stateful stream and internal buffer
 pub struct NatsClient {
-    tcp_client: TcpStream,
-    receive_buffer: Vec<u8>,
+    tcp_client: Connection,
+    parser: Parser,
     current_sid: AtomicUsize,
     verbose: bool,
     server_info: ServerInfo,
+    subscriptions: HashMap<u64, Subscription>,
+    handlers: Handlers,
+    /// known servers("host:port"), initial server and connect_urls notified by INFO
+    servers: Vec<String>,
+    /// index of connected server in servers
+    current_server: usize,
+    connect_option: Option<ConnectOption>,
+    read_timeout: Option<time::Duration>,
+    connect_timeout: Option<time::Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    ping_interval: Option<time::Duration>,
+    max_pings_out: u32,
+    /// PINGs sent by ping_interval and not answered yet
+    pings_out: u32,
+    read_buffer: Vec<u8>,
+    /// set when lame duck mode is notified and client should move to another server
+    lame_duck_migration: bool,
+    /// messages received while waiting for another response(e.g. PONG of flush)
+    pending_messages: VecDeque<NatsMessage>,
+    state: ConnectionState,
+    /// sid and subject prefix of subscription for request replies
+    response_subscription: Option<(u64, String)>,
+    response_count: u64,
+    /// reply subjects whose replies are waited by request or AsyncPublisher
+    awaited_replies: HashSet<String>,
+}
+
+/// socket connected to a server, errors are returned with the server
+struct Connection {
+    stream: TcpStream,
+    /// "host:port"
+    server: String,
+}
+
+impl Connection {
+    fn shutdown(&mut self) -> ::std::io::Result<()> {
+        self.stream.shutdown(net::Shutdown::Both)
+    }
+    /// connection lost(e.g. closed by server), not converted to NatsError::Timeout
+    fn lost(&self, kind: ErrorKind, reason: &str, operation: &str) -> NatsError {
+        NatsError::ConnectionError {
+            error: Error::new(kind, reason),
+            operation: operation.to_owned(),
+            server: self.server.clone(),
+        }
+    }
+}
+
+/// state of connection which is returned by NatsClient::status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// connection was lost and client is connecting to server in the pool
+    Reconnecting,
+    /// drain is in progress
+    Draining,
+    /// closed by close or drain, or connection was lost and could not be reestablished.
+    /// all operations return NatsError::ConnectionClosed
+    Closed,
+}
+
+/// connection event handlers
+#[derive(Default)]
+struct Handlers {
+    async_error: Option<AsyncErrorHandler>,
+    lame_duck: Option<LameDuckHandler>,
+    disconnected: Option<DisconnectedHandler>,
+    reconnected: Option<ReconnectedHandler>,
+    closed: Option<ClosedHandler>,
+    discovered_servers: Option<DiscoveredServersHandler>,
+}
+
+impl Handlers {
+    fn from_option(opt: &ConnectOption) -> Handlers {
+        let h = opt.handlers();
+        Handlers {
+            async_error: h.async_error.clone().map(|f| {
+                Box::new(move |e: &NatsServerError, sids: &[u64]| f(e, sids)) as AsyncErrorHandler
+            }),
+            lame_duck: h.lame_duck
+                .clone()
+                .map(|f| Box::new(move |info: &ServerInfo| f(info)) as LameDuckHandler),
+            disconnected: h.disconnected.clone().map(|f| {
+                Box::new(move |server: &str, e: &NatsError| f(server, e)) as DisconnectedHandler
+            }),
+            reconnected: h.reconnected
+                .clone()
+                .map(|f| Box::new(move |server: &str| f(server)) as ReconnectedHandler),
+            closed: h.closed.clone().map(|f| Box::new(move || f()) as ClosedHandler),
+            discovered_servers: h.discovered_servers.clone().map(|f| {
+                Box::new(move |servers: &[String]| f(servers)) as DiscoveredServersHandler
+            }),
+        }
+    }
+}
+
+/// state of subscription which is registered by subscribe
+struct Subscription {
+    subject: String,
+    queue: Option<String>,
+    /// remaining message count for unsubscribe_after
+    max_messages: Option<u64>,
+    delivered: u64,
+    error: Option<NatsServerError>,
 }
 
 /// NATS and another errors
@@ -38,8 +170,11 @@ pub struct NatsClient {
 pub enum NatsError {
     /// error message notified from server (see: https://nats.io/documentation/internals/nats-protocol/)
     ServerError(NatsServerError),
-    /// TCP/IP error,including wait timeout
-    ConnectionError(Error, String),
+    /// TCP/IP error, operation(error location) and server("host:port") are passed
+    ConnectionError { error: Error, operation: String, server: String },
+    /// operation did not complete within timeout.
+    /// error is set when read or write of socket timed out.
+    Timeout { operation: String, error: Option<Error> },
     /// message encoding error(not utf8 data)
     EncodingError(FromUtf8Error),
     /// parse failure from server
@@ -55,31 +190,189 @@ pub enum NatsError {
     WaitInfiniteLoop,
     /// Infinate loop detected in parsing MSG response
     MessageInfiniteLoop,
+    /// message size(headers + payload) is larger than max_payload of server.
+    /// nothing is sent to server.
+    MaxPayloadExceeded { size: usize, max: i64 },
+    /// header name or value cannot be sent
+    InvalidHeader(String),
+    /// subject, reply-to or queue name is not valid. nothing is sent to server.
+    InvalidSubject { subject: String, reason: &'static str },
+    /// operation on the client which is already closed
+    ConnectionClosed,
+    /// ConnectOption::echo is false but server protocol level does not support it
+    NoEchoNotSupported,
+    /// could not connect to any address which server("host:port") is resolved to.
+    /// attempted addresses and their errors are passed in tried order.
+    ConnectFailed { server: String, attempts: Vec<(net::SocketAddr, Error)> },
+    /// no subscriber for the request subject(status 503), subject is passed
+    NoResponders(String),
+    /// JetStream API returned error
+    JetStream(JetStreamApiError),
+    /// idle heartbeat of JetStream consumer was not received, location will be passed
+    MissedHeartbeat(String),
+    /// ordered JetStream consumer detected a gap.
+    /// last stream and consumer sequence delivered in order and received consumer sequence are passed.
+    ConsumerSequenceMismatch { stream_sequence: u64, consumer_sequence: u64, received: u64 },
+    /// JetStream consumer is looked up as another kind.
+    /// name of consumer is passed, push is true if it is push consumer.
+    ConsumerKindMismatch { name: String, push: bool },
+    /// configuration or argument of JetStream API(e.g. bucket name, key or history) is not valid.
+    /// name of the item, its value and reason are passed. nothing is sent to server.
+    InvalidConfig { name: &'static str, value: String, reason: &'static str },
+    /// link of object store cannot be added or followed, name of object and reason are passed
+    InvalidLink { name: String, reason: &'static str },
+    /// object which is not link already has the name, name is passed
+    ObjectExists(String),
+    /// IO error which is not caused by connection(e.g. reader passed to ObjectStore::put),
+    /// operation(error location) is passed
+    Io { error: Error, operation: String },
+    /// JetStream message cannot be acknowledged because it has no reply subject, subject is passed
+    NotAckable(String),
+    /// payload could not be serialized to or deserialized from JSON
+    JsonError(serde_json::Error),
 }
 
 impl fmt::Display for NatsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let msg = match self {
-            &NatsError::ServerError(ref v) => format!("server error:{}", v.error_message),
-            &NatsError::ConnectionError(ref e, ref loc) => {
-                format!("connection error({}): {}", loc, e)
+        match *self {
+            NatsError::ServerError(ref v) => write!(f, "server error:{}", v.error_message),
+            NatsError::ConnectionError { ref error, ref operation, ref server } => {
+                if server.is_empty() {
+                    write!(f, "connection error({}): {}", operation, error)
+                } else {
+                    write!(f, "connection error({}, server {}): {}", operation, server, error)
+                }
             }
-            &NatsError::EncodingError(ref e) => format!("encoding error:{}", e),
-            &NatsError::InvalidMessageArgument(ref args) => {
-                format!("invalid message argument:{:?}", args)
+            NatsError::Timeout { ref operation, ref error } => {
+                write!(f, "timeout({})", operation)?;
+                match *error {
+                    Some(ref e) => write!(f, ": {}", e),
+                    None => Ok(()),
+                }
             }
-            &NatsError::MessageParseError(ref name, ref v) => {
-                format!("parse error({}): {}", name, v)
+            NatsError::EncodingError(ref e) => write!(f, "encoding error:{}", e),
+            NatsError::InvalidMessageArgument(ref args) => {
+                write!(f, "invalid message argument:{:?}", args)
             }
-            &NatsError::UnknownResponse(ref v) => format!("unknown message:{}", v),
-            &NatsError::WaitInfiniteLoop => format!("infinite wait loop in wait_message"),
-            &NatsError::MessageInfiniteLoop => format!("infinite wait loop in parse_message")
-        };
-        write!(f, "{}", msg)
+            NatsError::MessageParseError(ref name, ref v) => {
+                write!(f, "parse error({}): {}", name, v)
+            }
+            NatsError::UnknownResponse(ref v) => write!(f, "unknown message:{}", v),
+            NatsError::WaitInfiniteLoop => write!(f, "infinite wait loop in wait_message"),
+            NatsError::MessageInfiniteLoop => write!(f, "infinite wait loop in parse_message"),
+            NatsError::MaxPayloadExceeded { size, max } => {
+                write!(f, "maximum payload exceeded: {} > {}", size, max)
+            }
+            NatsError::InvalidHeader(ref v) => write!(f, "invalid header:{}", v),
+            NatsError::InvalidSubject { ref subject, reason } => {
+                write!(f, "invalid subject({:?}): {}", subject, reason)
+            }
+            NatsError::ConnectionClosed => write!(f, "connection closed"),
+            NatsError::NoEchoNotSupported => write!(f, "server does not support echo=false"),
+            NatsError::NoResponders(ref subject) => write!(f, "no responders for {}", subject),
+            NatsError::JetStream(ref e) => write!(f, "jetstream error:{}", e),
+            NatsError::MissedHeartbeat(ref loc) => write!(f, "missed idle heartbeat({})", loc),
+            NatsError::JsonError(ref e) => write!(f, "json error:{}", e),
+            NatsError::ConsumerSequenceMismatch { stream_sequence, consumer_sequence, received } => {
+                write!(f,
+                       "consumer sequence mismatch: {} is received after {}(stream sequence {})",
+                       received,
+                       consumer_sequence,
+                       stream_sequence)
+            }
+            NatsError::ConsumerKindMismatch { ref name, push } => {
+                write!(f, "consumer {} is {} consumer", name, if push { "push" } else { "pull" })
+            }
+            NatsError::InvalidConfig { name, ref value, reason } => {
+                write!(f, "invalid {}({:?}): {}", name, value, reason)
+            }
+            NatsError::InvalidLink { ref name, reason } => write!(f, "invalid link({:?}): {}", name, reason),
+            NatsError::ObjectExists(ref name) => write!(f, "object {:?} already exists", name),
+            NatsError::Io { ref error, ref operation } => write!(f, "io error({}): {}", operation, error),
+            NatsError::NotAckable(ref subject) => write!(f, "message of {} has no ack subject", subject),
+            NatsError::ConnectFailed { ref server, ref attempts } => {
+                write!(f, "failed to connect {}:", server)?;
+                if attempts.is_empty() {
+                    return write!(f, " no address resolved");
+                }
+                for (addr, e) in attempts {
+                    write!(f, " {}({})", addr, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for NatsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            NatsError::ServerError(ref e) => Some(e),
+            NatsError::ConnectionError { ref error, .. } | NatsError::Io { ref error, .. } => Some(error),
+            NatsError::Timeout { ref error, .. } => error.as_ref().map(|e| e as &(dyn error::Error + 'static)),
+            NatsError::EncodingError(ref e) => Some(e),
+            NatsError::JetStream(ref e) => Some(e),
+            NatsError::JsonError(ref e) => Some(e),
+            NatsError::ConnectFailed { ref attempts, .. } => {
+                attempts.last().map(|(_, e)| e as &(dyn error::Error + 'static))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl NatsError {
+    /// returns true if same operation may succeed when it is retried
+    /// (after reconnecting if connection was lost).
+    ///
+    /// invalid arguments, permission errors and protocol errors are not retryable.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            NatsError::Timeout { .. } | NatsError::WaitInfiniteLoop | NatsError::MissedHeartbeat(_) => true,
+            NatsError::ConnectionError { ref error, .. } => {
+                matches!(error.kind(),
+                         ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut |
+                         ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset |
+                         ErrorKind::ConnectionAborted | ErrorKind::NotConnected |
+                         ErrorKind::BrokenPipe | ErrorKind::UnexpectedEof)
+            }
+            NatsError::ServerError(ref e) => e.is_retryable(),
+            // server may be restarting
+            NatsError::ConnectFailed { .. } => true,
+            _ => false,
+        }
+    }
+    /// operation did not complete until its deadline
+    pub(crate) fn timeout(operation: &str) -> NatsError {
+        NatsError::Timeout {
+            operation: operation.to_owned(),
+            error: None,
+        }
+    }
+    /// convert IO error of the server, read/write timeout becomes NatsError::Timeout
+    fn from_io(e: Error, operation: &str, server: &str) -> NatsError {
+        match e.kind() {
+            // read timeout is WouldBlock on unix and TimedOut on windows
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => {
+                NatsError::Timeout {
+                    operation: operation.to_owned(),
+                    error: Some(e),
+                }
+            }
+            _ => {
+                NatsError::ConnectionError {
+                    error: e,
+                    operation: operation.to_owned(),
+                    server: server.to_owned(),
+                }
+            }
+        }
     }
 }
 
 /// NATS normal responses (see: https://nats.io/documentation/internals/nats-protocol/)
+// Info is not boxed to keep the public variant unchanged
+#[allow(clippy::large_enum_variant)]
 pub enum NatsResponse {
     /// message from NATS services
     Msg(NatsMessage),
@@ -93,9 +386,74 @@ pub enum NatsResponse {
     Info(ServerInfo),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NatsServerError {
     pub error_message: String,
+    /// set when error_message is "Permissions Violation for ..."
+    pub permission_violation: Option<PermissionViolation>,
+}
+
+impl NatsServerError {
+    /// create from "-ERR" arguments
+    pub fn new(error_message: &str) -> NatsServerError {
+        NatsServerError {
+            error_message: error_message.to_owned(),
+            permission_violation: PermissionViolation::parse(error_message),
+        }
+    }
+    /// returns true if error is caused by temporary server state(e.g. "Stale Connection").
+    pub fn is_retryable(&self) -> bool {
+        if self.permission_violation.is_some() {
+            return false;
+        }
+        let msg = self.error_message.to_lowercase();
+        ["stale connection", "slow consumer", "maximum connections exceeded"]
+            .iter()
+            .any(|v| msg.contains(v))
+    }
+}
+
+/// operation denied by server permissions.
+/// server keeps connection alive when this error is sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PermissionViolation {
+    /// SUB was denied. queue is set when subscribed with queue group
+    Subscription { subject: String, queue: Option<String> },
+    /// PUB was denied
+    Publish { subject: String },
+    /// PUB was denied because of its reply subject
+    PublishReply { subject: String },
+}
+
+impl PermissionViolation {
+    /// parse "-ERR" message like `'Permissions Violation for Subscription to "foo"'`
+    ///
+    /// returns None if message is not permission violation
+    pub fn parse(error_message: &str) -> Option<PermissionViolation> {
+        let msg = error_message.trim().trim_matches('\'');
+        let rest = msg.strip_prefix("Permissions Violation for ")?;
+        if let Some(rest) = rest.strip_prefix("Subscription to ") {
+            let (subject, rest) = Self::take_quoted(rest)?;
+            let queue = match rest.trim_start().strip_prefix("using queue ") {
+                Some(q) => Some(Self::take_quoted(q)?.0),
+                None => None,
+            };
+            Some(PermissionViolation::Subscription { subject, queue })
+        } else if let Some(rest) = rest.strip_prefix("Publish with Reply of ") {
+            let (subject, _) = Self::take_quoted(rest)?;
+            Some(PermissionViolation::PublishReply { subject })
+        } else if let Some(rest) = rest.strip_prefix("Publish to ") {
+            let (subject, _) = Self::take_quoted(rest)?;
+            Some(PermissionViolation::Publish { subject })
+        } else {
+            None
+        }
+    }
+    fn take_quoted(s: &str) -> Option<(String, &str)> {
+        let s = s.strip_prefix('"')?;
+        let end = s.find('"')?;
+        Some((s[..end].to_owned(), &s[end + 1..]))
+    }
 }
 
 impl fmt::Display for NatsServerError {
@@ -104,12 +462,55 @@ impl fmt::Display for NatsServerError {
     }
 }
 
-#[derive(Debug)]
+impl error::Error for NatsServerError {}
+
+/// message delivered to subscription
+///
+/// use NatsMessage::new or Default to build it, fields may be added(e.g. headers).
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct NatsMessage {
     pub sid: u64,
     pub data: Vec<u8>,
     pub subject: String,
     pub reply: Option<String>,
+    /// set when message is received by HMSG
+    pub headers: Option<Headers>,
+}
+
+impl NatsMessage {
+    /// message without headers
+    pub fn new(sid: u64, subject: &str, reply: Option<&str>, data: Vec<u8>) -> NatsMessage {
+        NatsMessage {
+            sid,
+            data,
+            subject: subject.to_owned(),
+            reply: reply.map(|v| v.to_owned()),
+            headers: None,
+        }
+    }
+    /// status code of header-only message(e.g. 503 for no responders, 100 for flow control)
+    pub fn status(&self) -> Option<u16> {
+        self.headers.as_ref().and_then(|h| h.status())
+    }
+    /// server notified that nobody subscribes the request subject
+    pub fn is_no_responders(&self) -> bool {
+        self.data.is_empty() && self.status() == Some(503)
+    }
+    /// deserialize payload from JSON, NatsError::JsonError is returned if it fails
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(&self.data).map_err(NatsError::JsonError)
+    }
+}
+
+/// random token for inbox, unique in the process
+pub(crate) fn unique_token() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::Relaxed));
+    if let Ok(elapsed) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    format!("{:016x}{:08x}", hasher.finish(), process::id())
 }
 
 impl NatsClient {
@@ -147,77 +548,164 @@ impl NatsClient {
                     read_timeout: Option<time::Duration>,
                     opt: Option<&ConnectOption>)
                     -> Result<Self> {
-        let mut client = match TcpStream::connect(format!("{}:{}", host, port)) {
-            Ok(v) => v,
-            Err(e) => return Err(NatsError::ConnectionError(e, "NatsClient::new".to_owned())),
-        };
-        match read_timeout {
-            Some(t) => Self::set_read_timeout_internal(&mut client, Some(t))?,
-            None => {}
-        };
-        let mut buf = Vec::new();
-        buf.reserve(1024);
-        let mut infobuf = [0u8; 1024];
-        let bytesread = Self::read_request(&mut client,
-                                           &mut infobuf,
-                                           "NatsClient::new::get_server_info")?;
-        let parsed = match String::from_utf8(infobuf[0..bytesread].to_vec()) {
-            Ok(v) => v,
-            Err(e) => return Err(NatsError::EncodingError(e)),
+        let mut options = Options::new().server(&format!("{}:{}", host, port));
+        options.read_timeout = read_timeout;
+        Self::connect_internal(&options, opt)
+    }
+    /// Constructs a new NatsClient by Options::connect
+    pub(crate) fn connect_with_options(options: &Options) -> Result<Self> {
+        Self::connect_internal(options, Some(&options.connect_option))
+    }
+    fn connect_internal(options: &Options, opt: Option<&ConnectOption>) -> Result<Self> {
+        let servers = if options.servers.is_empty() {
+            vec![::options::DEFAULT_SERVER.to_owned()]
+        } else {
+            options.servers.clone()
         };
-        let args: Vec<&str> = parsed.split(" ").collect();
-        let server_info: ServerInfo = match NatsClient::parse_server_info(&args[1..]) {
-            Ok(v) => {
-                match v {
-                    NatsResponse::Info(v) => v,
-                    _ => return Err(NatsError::UnknownResponse(parsed.clone())),
+        if options.tls_required {
+            return Err(Self::tls_not_supported("NatsClient::new", &servers[0]));
+        }
+        let mut last_error = None;
+        for (index, server) in servers.iter().enumerate() {
+            let (client, parser, server_info) =
+                match Self::connect_server(server,
+                                           options.connect_timeout,
+                                           options.read_timeout,
+                                           options.read_buffer_size) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!("failed to connect {}: {}", server, e);
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+            let mut ret = NatsClient {
+                tcp_client: client,
+                parser,
+                current_sid: AtomicUsize::new(0),
+                verbose: opt.is_none_or(|v| v.verbose),
+                server_info: ServerInfo::default(),
+                subscriptions: HashMap::new(),
+                handlers: opt.map(Handlers::from_option).unwrap_or_default(),
+                servers: servers.clone(),
+                current_server: index,
+                connect_option: opt.cloned(),
+                read_timeout: options.read_timeout,
+                connect_timeout: options.connect_timeout,
+                // no trial is the same as disabled reconnect
+                reconnect: options.reconnect.filter(|v| v.max_reconnects > 0),
+                ping_interval: options.ping_interval,
+                max_pings_out: options.max_pings_out,
+                pings_out: 0,
+                read_buffer: vec![0u8; options.read_buffer_size],
+                lame_duck_migration: false,
+                pending_messages: VecDeque::new(),
+                state: ConnectionState::Connected,
+                response_subscription: None,
+                response_count: 0,
+                awaited_replies: HashSet::new(),
+            };
+            ret.update_server_info(server_info);
+            if let Some(opt) = opt {
+                if let Err(e) = ret.send_connect_option(opt) {
+                    // try next server like failure of TCP connection
+                    warn!("failed to send CONNECT to {}: {}", server, e);
+                    last_error = Some(e);
+                    continue;
                 }
             }
-            Err(e) => return Err(e),
+            return Ok(ret);
+        }
+        Err(last_error.expect("server pool is not empty"))
+    }
+    /// connect to "host:port" and receive INFO
+    ///
+    /// connect_timeout is applied to each TCP connection and receiving INFO
+    fn connect_server(server: &str,
+                      connect_timeout: Option<time::Duration>,
+                      read_timeout: Option<time::Duration>,
+                      read_buffer_size: usize)
+                      -> Result<(Connection, Parser, ServerInfo)> {
+        let mut client = Connection {
+            stream: Self::connect_tcp(server, connect_timeout)?,
+            server: server.to_owned(),
+        };
+        Self::set_read_timeout_internal(&mut client, connect_timeout.or(read_timeout))?;
+        let mut parser = Parser::new();
+        let mut buf = vec![0u8; read_buffer_size];
+        let server_info = loop {
+            match parser.next_op()? {
+                Some(ServerOp::Info(v)) => break *v,
+                Some(op) => return Err(NatsError::UnknownResponse(format!("{:?}", op))),
+                None => Self::read_to_parser(&mut client,
+                                             &mut parser,
+                                             &mut buf,
+                                             "NatsClient::new::get_server_info")?,
+            }
         };
         debug!("{:?}", server_info);
-        let mut ret = NatsClient {
-            tcp_client: client,
-            receive_buffer: buf,
-            current_sid: AtomicUsize::new(0),
-            verbose: match &opt {
-                &Some(ref v) => v.verbose,
-                &None => true,
-            },
-            server_info: server_info,
+        if server_info.tls_required {
+            return Err(Self::tls_not_supported("NatsClient::new::get_server_info", server));
+        }
+        Self::set_read_timeout_internal(&mut client, read_timeout)?;
+        Ok((client, parser, server_info))
+    }
+    /// try all addresses(IPv4 and IPv6) which server is resolved to in order
+    fn connect_tcp(server: &str, timeout: Option<time::Duration>) -> Result<TcpStream> {
+        let addrs = match server.to_socket_addrs() {
+            Ok(v) => v,
+            Err(e) => return Err(NatsError::from_io(e, "NatsClient::new::resolve", server)),
         };
-        match opt {
-            Some(opt) => ret.send_connect_option(&opt)?,
-            None => {}
+        let mut attempts = Vec::new();
+        for addr in addrs {
+            let ret = match timeout {
+                Some(t) => TcpStream::connect_timeout(&addr, t),
+                None => TcpStream::connect(addr),
+            };
+            match ret {
+                Ok(v) => return Ok(v),
+                Err(e) => {
+                    debug!("failed to connect {}: {}", addr, e);
+                    attempts.push((addr, e));
+                }
+            }
         }
-        Ok(ret)
+        Err(NatsError::ConnectFailed {
+            server: server.to_owned(),
+            attempts,
+        })
     }
-    fn set_read_timeout_internal(client: &mut TcpStream, t: Option<time::Duration>) -> Result<()> {
-        match client.set_read_timeout(t) {
+    fn tls_not_supported(from: &str, server: &str) -> NatsError {
+        NatsError::ConnectionError {
+            error: Error::new(ErrorKind::Unsupported, "TLS is not supported"),
+            operation: from.to_owned(),
+            server: server.to_owned(),
+        }
+    }
+    fn set_read_timeout_internal(client: &mut Connection, t: Option<time::Duration>) -> Result<()> {
+        match client.stream.set_read_timeout(t) {
             Ok(_) => Ok(()),
-            Err(e) => {
-                return Err(NatsError::ConnectionError(e,
-                                                      "NatsClient::set_read_timeout_internal"
-                                                          .to_owned()))
-            }
+            Err(e) => Err(NatsError::from_io(e, "NatsClient::set_read_timeout_internal", &client.server)),
         }
     }
     fn send_connect_option(&mut self, opt: &ConnectOption) -> Result<()> {
-        let connectstr = match serde_json::to_string(opt) {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(NatsError::MessageParseError(format!("json serialization error"),
-                                                        format!("{:?}",e)))
-            }
-        };
-        Self::write_request(&mut self.tcp_client,
-                            format!("CONNECT {}\r\n", connectstr).as_bytes(),
-                            "NatsClient::send_connect_option")?;
+        // no echo is supported from protocol level 1
+        if !opt.echo && self.server_info.proto < 1 {
+            return Err(NatsError::NoEchoNotSupported);
+        }
+        let connectstr = serde_json::to_string(opt).map_err(NatsError::JsonError)?;
+        let mut buf = Vec::new();
+        protocol::encode_connect(&connectstr, &mut buf);
+        Self::write_request(&mut self.tcp_client, &buf, "NatsClient::send_connect_option")?;
         if opt.verbose {
             let mut buf = [0u8; 32];
-            Self::read_request(&mut self.tcp_client,
-                               &mut buf,
-                               "NatsClient::send_connect_option")?;
+            if Self::read_request(&mut self.tcp_client,
+                                  &mut buf,
+                                  "NatsClient::send_connect_option")? == 0 {
+                return Err(self.tcp_client.lost(ErrorKind::UnexpectedEof,
+                                                "connection closed by server",
+                                                "NatsClient::send_connect_option"));
+            }
         }
         // Self::write_request(&self.tcp_client, )
         Ok(())
@@ -232,23 +720,73 @@ impl NatsClient {
     /// c.publish("subject", None, &[0u8;4]).unwrap();
     /// ```
     pub fn publish(&mut self, subject: &str, reply_to: Option<&str>, data: &[u8]) -> Result<u64> {
-        let datastr: String = match reply_to {
-            Some(v) => format!("PUB {} {} {}\r\n", subject, v, data.len()),
-            None => format!("PUB {} {}\r\n", subject, data.len()),
+        self.publish_internal(subject, reply_to, None, data)
+    }
+    /// Publish value serialized as JSON
+    ///
+    /// NatsError::JsonError is returned if value cannot be serialized.
+    pub fn publish_json<T: Serialize>(&mut self, subject: &str, reply_to: Option<&str>, value: &T) -> Result<u64> {
+        let data = serde_json::to_vec(value).map_err(NatsError::JsonError)?;
+        self.publish_internal(subject, reply_to, None, &data)
+    }
+    /// Publish message with headers(HPUB)
+    ///
+    /// server must support headers and ConnectOption::headers must be true.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate simple_nats_client;
+    /// use simple_nats_client::nats_client::{ConnectOption, Headers, NatsClient};
+    /// let mut c = NatsClient::new_with_option("127.0.0.1", 4222, None, Some(&ConnectOption::new())).unwrap();
+    /// let mut headers = Headers::new();
+    /// headers.insert("key", "value");
+    /// c.publish_with_headers("subject", None, &headers, &[0u8;4]).unwrap();
+    /// ```
+    pub fn publish_with_headers(&mut self,
+                                subject: &str,
+                                reply_to: Option<&str>,
+                                headers: &Headers,
+                                data: &[u8])
+                                -> Result<u64> {
+        self.publish_internal(subject, reply_to, Some(headers), data)
+    }
+    fn publish_internal(&mut self,
+                        subject: &str,
+                        reply_to: Option<&str>,
+                        headers: Option<&Headers>,
+                        data: &[u8])
+                        -> Result<u64> {
+        self.check_open()?;
+        ::subject::validate_publish_subject(subject)?;
+        if let Some(v) = reply_to {
+            ::subject::validate_publish_subject(v)?;
+        }
+        let header_len = match headers {
+            Some(h) => {
+                h.validate()?;
+                h.encoded_len()
+            }
+            None => 0,
         };
-        // debug!("publishing string:{}", datastr);
-        Self::write_request(&mut self.tcp_client,
-                            datastr.as_bytes(),
-                            "NatsClient::publish")?;
-        Self::write_request(&mut self.tcp_client, data, "NatsClient::publish")?;
-        let mut crlf_bytes: [u8; 2] = [0x0d, 0x0a];
-        Self::write_request(&mut self.tcp_client, &mut crlf_bytes, "NatsClient::publish")?;
+        self.check_payload_size(header_len + data.len())?;
+        let mut buf = Vec::with_capacity(header_len + data.len() + 64);
+        protocol::encode_pub(subject, reply_to, headers, data, &mut buf);
+        self.write_or_recover(&buf, "NatsClient::publish", true)?;
         if self.verbose {
-            self.consume_verbose_response()?;
+            self.consume_verbose_or_recover()?;
             // debug!("publish({}): consume verbose message done", subject);
         }
         Ok(0)
     }
+    /// max_payload <= 0 means server does not notify the limit
+    fn check_payload_size(&self, size: usize) -> Result<()> {
+        let max = self.server_info.max_payload;
+        if max > 0 && size as u64 > max as u64 {
+            return Err(NatsError::MaxPayloadExceeded { size, max });
+        }
+        Ok(())
+    }
     /// Subscribe specified subject, returns Subscription ID
     /// 
     /// if you want to get events, do wait_message.
@@ -264,15 +802,30 @@ impl NatsClient {
     /// let sid2 = c.subscribe("subject2", Some("qname")).unwrap();
     /// ```
     pub fn subscribe(&mut self, subject: &str, queue: Option<&str>) -> Result<u64> {
+        self.check_open()?;
+        ::subject::validate_subject(subject)?;
+        if let Some(v) = queue {
+            ::subject::validate_queue_name(v)?;
+        }
         let sid = self.current_sid.fetch_add(1, Ordering::Relaxed);
-        let datastr: String = match queue {
-            Some(v) => format!("SUB {} {} {}\r\n", subject, v, sid),
-            None => format!("SUB {} {}\r\n", subject, sid),
-        };
-        debug!("subscribing string:{}", datastr);
-        Self::write_request(&mut self.tcp_client, &mut datastr.as_bytes(), "subscribe")?;
+        let mut buf = Vec::new();
+        protocol::encode_sub(subject, queue, sid as u64, &mut buf);
+        debug!("subscribing string:{}", String::from_utf8_lossy(&buf));
+        // registered first, so that SUB is sent again if client reconnects
+        self.subscriptions.insert(sid as u64,
+                                  Subscription {
+                                      subject: subject.to_owned(),
+                                      queue: queue.map(|q| q.to_owned()),
+                                      max_messages: None,
+                                      delivered: 0,
+                                      error: None,
+                                  });
+        if let Err(e) = self.write_or_recover(&buf, "subscribe", false) {
+            self.subscriptions.remove(&(sid as u64));
+            return Err(e);
+        }
         if self.verbose {
-            self.consume_verbose_response()?;
+            self.consume_verbose_or_recover()?;
             debug!("subscribe({}):consume verbose message done", subject);
         }
         Ok(sid as u64)
@@ -292,114 +845,406 @@ impl NatsClient {
     ///   _ => println!("another message")
     /// };
     /// ```
+    ///
+    /// if reconnect is enabled, lost connection is reestablished to a server in the pool
+    /// and subscriptions are sent again.
+    ///
+    /// replies to the response inbox of request are not returned.
+    /// replies which are still waited(e.g. acknowledgements of AsyncPublisher) are kept for the waiter,
+    /// and replies which arrive after request is timed out are discarded.
     pub fn wait_message(&mut self) -> Result<NatsResponse> {
-        for i in 0..1000 {
-            if let Some(crlf_index) = Self::find_crlf(self.receive_buffer.as_slice()) {
-                let header_string: String =
-                    match String::from_utf8(self.receive_buffer[0..crlf_index].to_vec()) {
-                        Ok(v) => v,
-                        Err(e) => return Err(NatsError::EncodingError(e)),
-                    };
-                let headers: Vec<&str> = header_string.split(" ").collect();
-                // remove consumed data
-                self.receive_buffer = self.receive_buffer[crlf_index + 2..].to_vec();
-                return match headers[0] {
-                           "-ERR" => {
-                               Err(NatsError::ServerError(NatsServerError {
-                                                              error_message: headers[1..].join(" "),
-                                                          }))
-                           }
-                           "+OK" => Ok(NatsResponse::Ok),
-                           "MSG" => self.parse_message(&headers[1..]),
-                           "PING" => Ok(NatsResponse::Ping),
-                           "PONG" => Ok(NatsResponse::Pong),
-                           "INFO" => NatsClient::parse_server_info(&headers[1..]),
-                           _ => Err(NatsError::UnknownResponse(headers.join(""))),
-                       };
+        self.check_open()?;
+        if let Some(msg) = self.pop_pending_message() {
+            return Ok(NatsResponse::Msg(msg));
+        }
+        loop {
+            if let Some(op) = self.parser.next_op()? {
+                if self.is_keepalive_pong(&op) {
+                    continue;
+                }
+                match self.handle_op(op)? {
+                    NatsResponse::Msg(msg) => {
+                        if !self.is_response(&msg) {
+                            return Ok(NatsResponse::Msg(msg));
+                        }
+                        if self.awaited_replies.contains(&msg.subject) {
+                            self.pending_messages.push_back(msg);
+                        } else {
+                            debug!("discard reply which nobody waits: {}", msg.subject);
+                        }
+                        continue;
+                    }
+                    resp => return Ok(resp),
+                }
+            }
+            if self.lame_duck_migration {
+                // messages already received from old server have been returned
+                self.lame_duck_migration = false;
+                self.leave_lame_duck_server();
+                if let Some(msg) = self.pop_pending_message() {
+                    return Ok(NatsResponse::Msg(msg));
+                }
+            }
+            debug!("reading nats message from server");
+            match self.read_with_keepalive("NatsClient::wait_message") {
+                Ok(()) => {}
+                Err(e @ NatsError::ConnectionError { .. }) => {
+                    if !self.recover_connection(&e) {
+                        return Err(e);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    /// pop message for wait_message, waited replies are left in the queue
+    fn pop_pending_message(&mut self) -> Option<NatsMessage> {
+        let mut i = 0;
+        while i < self.pending_messages.len() {
+            let msg = &self.pending_messages[i];
+            if !self.is_response(msg) {
+                return self.pending_messages.remove(i);
+            }
+            if self.awaited_replies.contains(&msg.subject) {
+                i += 1;
             } else {
-                let mut buf = [0u8; 512];
-                debug!("reading nats message from server,{}", i);
-                let bytesread =
-                    Self::read_request(&mut self.tcp_client, &mut buf, "NatsClient::wait_message")?;
-                self.receive_buffer
-                    .append(&mut buf[0..bytesread].to_vec());
+                debug!("discard reply which nobody waits: {}", msg.subject);
+                self.pending_messages.remove(i);
             }
         }
-        Err(NatsError::WaitInfiniteLoop)
+        None
     }
-    fn parse_message(&mut self, args: &[&str]) -> Result<NatsResponse> {
-        debug!("parsing message:{:?}", args);
-        let (subject, sidstr, reply, msgsizestr) = match args.len() {
-            3 => (args[0], args[1], None as Option<&str>, args[2]),
-            4 => (args[0], args[1], Some(args[2]), args[3]),
-            _ => {
-                return Err(NatsError::InvalidMessageArgument(args.iter()
-                                                                 .map(|&x| String::from(x))
-                                                                 .collect()))
+    /// message to response inbox of request
+    fn is_response(&self, msg: &NatsMessage) -> bool {
+        self.response_subscription.as_ref().is_some_and(|v| v.0 == msg.sid)
+    }
+    /// handle connection lost while reading or writing.
+    ///
+    /// disconnected handler is called, then client reconnects to a server in the pool if reconnect is enabled.
+    /// returns true if connection is reestablished, otherwise client is closed.
+    fn recover_connection(&mut self, e: &NatsError) -> bool {
+        warn!("connection lost: {}", e);
+        if let Some(ref mut handler) = self.handlers.disconnected {
+            handler(&self.servers[self.current_server], e);
+        }
+        // drain closes connection in any case
+        if self.reconnect.is_none() || self.state == ConnectionState::Draining {
+            self.tcp_client.shutdown().unwrap_or_default();
+            self.set_closed();
+            return false;
+        }
+        self.state = ConnectionState::Reconnecting;
+        match self.reconnect_internal(false) {
+            Ok((mut old, _)) => {
+                old.shutdown().unwrap_or_default();
+                self.state = ConnectionState::Connected;
+                true
             }
-        };
-        let sid = match u64::from_str(sidstr) {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(NatsError::MessageParseError(format!("sid:{}", e), sidstr.to_owned()))
+            Err(re) => {
+                warn!("failed to reconnect: {}", re);
+                self.tcp_client.shutdown().unwrap_or_default();
+                self.set_closed();
+                false
             }
-        };
-        let msgsize = match i64::from_str(msgsizestr) {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(NatsError::MessageParseError(format!("msgsize:{}", e),
-                                                        msgsizestr.to_owned()))
+        }
+    }
+    /// pass error to recover_connection if connection is lost, error is returned in any case
+    fn fail_with<R>(&mut self, ret: Result<R>) -> Result<R> {
+        if let Err(ref e @ NatsError::ConnectionError { .. }) = ret {
+            self.recover_connection(e);
+        }
+        ret
+    }
+    /// write to server, connection lost is handled by recover_connection.
+    ///
+    /// data is written again to new connection if resend is true,
+    /// it is not needed for SUB and UNSUB because they are applied by resubscribe.
+    fn write_or_recover(&mut self, data: &[u8], from: &str, resend: bool) -> Result<()> {
+        match Self::write_request(&mut self.tcp_client, data, from) {
+            Ok(_) => Ok(()),
+            Err(e @ NatsError::ConnectionError { .. }) => {
+                if !self.recover_connection(&e) {
+                    return Err(e);
+                }
+                if resend {
+                    Self::write_request(&mut self.tcp_client, data, from)?;
+                }
+                Ok(())
             }
-        };
-        debug!("subject = {}, sid = {}, msgsize = {}, reply = {:?}, current buffer length = {}",
-               subject,
-               sid,
-               msgsize,
-               reply,
-               self.receive_buffer.len());
-        let copylen = cmp::min((msgsize + 2) as usize, self.receive_buffer.len());
-        let mut data: Vec<u8> = vec![0u8;(msgsize + 2) as usize];
-        data[0..copylen].copy_from_slice(&self.receive_buffer[0..copylen]);
-        let remaining_length = self.receive_buffer.len() - copylen;
-        // in-place copy supported from nightly?
-        let src_slice: Vec<u8> = self.receive_buffer[copylen..].iter().cloned().collect();
-        self.receive_buffer[0..remaining_length].copy_from_slice(&src_slice[..]);
-        self.receive_buffer.truncate(remaining_length);
-        let mut total_bytes_read: usize = copylen;
-        for i in 0..(msgsize + 1) * 100 {
-            debug!("consuming buffer:{},{},{}", i, total_bytes_read, msgsize);
-            // msgsize + CRLF
-            if total_bytes_read >= ((msgsize + 2) as usize) {
-                // remove CRLF
-                data.truncate(msgsize as usize);
-                return Ok(NatsResponse::Msg(NatsMessage {
-                                                subject: subject.to_owned(),
-                                                sid: sid,
-                                                data: data,
-                                                reply: match reply {
-                                                    Some(v) => Some(v.to_owned()),
-                                                    None => None,
-                                                },
-                                            }));
-            } else {
-                debug!("recv from remote");
-                let bytesread = Self::read_request(&mut self.tcp_client,
-                                                   &mut data[total_bytes_read..],
-                                                   "NatsClient::parse_message")?;
-                total_bytes_read += bytesread;
-                // data.append(&mut buf[0..bytesread]);
-                // self.receive_buffer
-                //     .append(&mut buf[0..bytesread].to_vec());
+            Err(e) => Err(e),
+        }
+    }
+    /// move to another server, then receive messages which old server has already sent.
+    ///
+    /// subscriptions are sent to new server before they are unsubscribed from old server,
+    /// so messages published while moving may be received twice.
+    /// current connection is kept if no other server is available.
+    fn leave_lame_duck_server(&mut self) {
+        let pings_out = self.pings_out;
+        self.state = ConnectionState::Reconnecting;
+        let migrated = self.reconnect_internal(true);
+        self.state = ConnectionState::Connected;
+        match migrated {
+            Ok((old, parser)) => self.drain_old_connection(old, parser, pings_out),
+            Err(e) => warn!("failed to leave lame duck server, keep current connection: {}", e),
+        }
+    }
+    /// unsubscribe all from old connection and queue its messages until PONG, then close it
+    fn drain_old_connection(&mut self, mut old: Connection, mut parser: Parser, mut pings_out: u32) {
+        let from = "NatsClient::drain_old_connection";
+        let timeout = self.connect_timeout.or(self.read_timeout).unwrap_or(LAME_DUCK_DRAIN_TIMEOUT);
+        let deadline = time::Instant::now() + timeout;
+        let mut sids: Vec<u64> = self.subscriptions.keys().cloned().collect();
+        sids.sort();
+        let mut buf = Vec::new();
+        for sid in sids {
+            protocol::encode_unsub(sid, None, &mut buf);
+        }
+        buf.extend_from_slice(&protocol::ClientOp::Ping.encode());
+        let mut ret = Self::write_request(&mut old, &buf, from).map(|_| ());
+        while ret.is_ok() {
+            ret = match parser.next_op() {
+                Ok(Some(ServerOp::Msg(msg))) => {
+                    self.count_delivered(msg.sid);
+                    self.pending_messages.push_back(msg);
+                    Ok(())
+                }
+                Ok(Some(ServerOp::Pong)) if pings_out > 0 => {
+                    pings_out -= 1;
+                    Ok(())
+                }
+                Ok(Some(ServerOp::Pong)) => break,
+                Ok(Some(ServerOp::Ping)) => {
+                    Self::write_request(&mut old, &protocol::ClientOp::Pong.encode(), from).map(|_| ())
+                }
+                Ok(Some(op)) => {
+                    debug!("ignore response from lame duck server: {:?}", op);
+                    Ok(())
+                }
+                Ok(None) => {
+                    let now = time::Instant::now();
+                    if now >= deadline {
+                        Err(NatsError::timeout(from))
+                    } else {
+                        Self::set_read_timeout_internal(&mut old, Some(deadline - now))
+                            .and_then(|_| Self::read_to_parser(&mut old, &mut parser, &mut self.read_buffer, from))
+                    }
+                }
+                Err(e) => Err(e),
+            };
+        }
+        if let Err(e) = ret {
+            warn!("failed to drain lame duck server: {}", e);
+        }
+        old.shutdown().unwrap_or_default();
+    }
+    fn handle_op(&mut self, op: ServerOp) -> Result<NatsResponse> {
+        match op {
+            ServerOp::Err(e) => {
+                self.handle_server_error(&e);
+                Err(NatsError::ServerError(e))
+            }
+            ServerOp::Ok => Ok(NatsResponse::Ok),
+            ServerOp::Msg(msg) => {
+                self.count_delivered(msg.sid);
+                Ok(NatsResponse::Msg(msg))
+            }
+            ServerOp::Ping => Ok(NatsResponse::Ping),
+            ServerOp::Pong => Ok(NatsResponse::Pong),
+            ServerOp::Info(info) => {
+                debug!("server info updated:{:?}", info);
+                let lame_duck = info.ldm && !self.server_info.ldm;
+                self.update_server_info((*info).clone());
+                if lame_duck {
+                    warn!("server {} entered lame duck mode", self.servers[self.current_server]);
+                    if let Some(ref mut handler) = self.handlers.lame_duck {
+                        handler(&info);
+                    }
+                    self.lame_duck_migration = self.reconnect.is_some();
+                }
+                Ok(NatsResponse::Info(*info))
             }
         }
-        Err(NatsError::MessageInfiniteLoop)
     }
-    fn parse_server_info(args: &[&str]) -> Result<NatsResponse> {
-        let svr_info: ServerInfo = match serde_json::from_str(&args.join(" ")) {
-            Ok(v) => v,
-            Err(e) => panic!("{}", e),
+    /// replace ServerInfo and add connect_urls to server pool
+    fn update_server_info(&mut self, info: ServerInfo) {
+        let mut discovered = Vec::new();
+        for url in &info.connect_urls {
+            if !self.servers.contains(url) && !discovered.contains(url) {
+                debug!("server discovered: {}", url);
+                discovered.push(url.clone());
+            }
+        }
+        self.servers.extend_from_slice(&discovered);
+        self.server_info = info;
+        if discovered.is_empty() {
+            return;
+        }
+        if let Some(ref mut handler) = self.handlers.discovered_servers {
+            handler(&discovered);
+        }
+    }
+    /// connect to another server in the pool, then send CONNECT and active subscriptions.
+    ///
+    /// current server is tried last, or is not tried if exclude_current is true.
+    /// servers are tried ReconnectPolicy::max_reconnects times, except migration from lame duck server.
+    /// old connection and its parser are returned, caller must close it.
+    fn reconnect_internal(&mut self, exclude_current: bool) -> Result<(Connection, Parser)> {
+        let policy = self.reconnect.unwrap_or_default();
+        let trials = if exclude_current { 1 } else { policy.max_reconnects };
+        let mut last_error = None;
+        for trial in 0..trials {
+            if trial > 0 {
+                thread::sleep(policy.reconnect_wait);
+            }
+            let count = self.servers.len();
+            for i in 1..count + 1 {
+                let index = (self.current_server + i) % count;
+                if exclude_current && index == self.current_server {
+                    continue;
+                }
+                let server = self.servers[index].clone();
+                match self.connect_to(&server) {
+                    Ok(old) => {
+                        info!("reconnected to {}", server);
+                        self.current_server = index;
+                        if let Some(ref mut handler) = self.handlers.reconnected {
+                            handler(&server);
+                        }
+                        return Ok(old);
+                    }
+                    Err(e) => {
+                        warn!("failed to connect {}: {}", server, e);
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            self.tcp_client.lost(ErrorKind::NotConnected, "no other server to connect", "NatsClient::reconnect")
+        }))
+    }
+    /// switch to new connection, current connection is kept if it fails
+    fn connect_to(&mut self, server: &str) -> Result<(Connection, Parser)> {
+        let (client, parser, server_info) =
+            Self::connect_server(server,
+                                 self.connect_timeout,
+                                 self.read_timeout,
+                                 self.read_buffer.len())?;
+        let old_client = mem::replace(&mut self.tcp_client, client);
+        let old_parser = mem::replace(&mut self.parser, parser);
+        let old_info = mem::replace(&mut self.server_info, server_info.clone());
+        let ret = self.connect_option
+            .clone()
+            .map_or(Ok(()), |opt| self.send_connect_option(&opt))
+            .and_then(|_| self.resubscribe());
+        if let Err(e) = ret {
+            let mut client = mem::replace(&mut self.tcp_client, old_client);
+            client.shutdown().unwrap_or_default();
+            self.parser = old_parser;
+            self.server_info = old_info;
+            return Err(e);
+        }
+        self.server_info = old_info;
+        self.pings_out = 0;
+        self.update_server_info(server_info);
+        Ok((old_client, old_parser))
+    }
+    /// send SUB (and UNSUB for unsubscribe_after) of active subscriptions to new connection
+    fn resubscribe(&mut self) -> Result<()> {
+        let mut sids: Vec<u64> = self.subscriptions
+            .iter()
+            .filter(|&(_, sub)| sub.error.is_none())
+            .map(|(sid, _)| *sid)
+            .collect();
+        sids.sort();
+        for sid in sids {
+            let mut buf = Vec::new();
+            {
+                let sub = &self.subscriptions[&sid];
+                protocol::encode_sub(&sub.subject, sub.queue.as_deref(), sid, &mut buf);
+                if let Some(max) = sub.max_messages {
+                    protocol::encode_unsub(sid, Some(max - sub.delivered), &mut buf);
+                }
+            }
+            Self::write_request(&mut self.tcp_client, &buf, "NatsClient::resubscribe")?;
+            if self.verbose {
+                self.consume_verbose_response()?;
+            }
+        }
+        Ok(())
+    }
+    /// read from socket for wait_message.
+    ///
+    /// if ping interval is set, PING is sent when nothing is received for the interval,
+    /// and connection is regarded as lost when max_pings_out PINGs are not answered.
+    fn read_with_keepalive(&mut self, from: &str) -> Result<()> {
+        let interval = match self.ping_interval {
+            Some(v) => v,
+            None => {
+                return Self::read_to_parser(&mut self.tcp_client,
+                                            &mut self.parser,
+                                            &mut self.read_buffer,
+                                            from)
+            }
         };
-        Ok(NatsResponse::Info(svr_info))
+        let deadline = self.read_timeout.map(|t| time::Instant::now() + t);
+        let ret = self.read_with_ping(interval, deadline, from);
+        let timeout = self.read_timeout;
+        let restored = Self::set_read_timeout_internal(&mut self.tcp_client, timeout);
+        ret.and(restored)
+    }
+    fn read_with_ping(&mut self,
+                      interval: time::Duration,
+                      deadline: Option<time::Instant>,
+                      from: &str)
+                      -> Result<()> {
+        loop {
+            let mut wait = interval;
+            if let Some(deadline) = deadline {
+                let now = time::Instant::now();
+                if now >= deadline {
+                    return Err(NatsError::timeout(from));
+                }
+                wait = wait.min(deadline - now);
+            }
+            Self::set_read_timeout_internal(&mut self.tcp_client, Some(wait))?;
+            match Self::read_to_parser(&mut self.tcp_client,
+                                       &mut self.parser,
+                                       &mut self.read_buffer,
+                                       from) {
+                Err(NatsError::Timeout { .. }) => {}
+                ret => return ret,
+            }
+            if deadline.is_some_and(|d| time::Instant::now() >= d) {
+                return Err(NatsError::timeout(from));
+            }
+            if self.pings_out >= self.max_pings_out {
+                return Err(self.tcp_client.lost(ErrorKind::TimedOut, "stale connection", from));
+            }
+            debug!("sending PING for keepalive");
+            self.pings_out += 1;
+            Self::write_request(&mut self.tcp_client, &protocol::ClientOp::Ping.encode(), from)?;
+        }
+    }
+    /// PONG for PING of ping interval is not returned to caller
+    fn is_keepalive_pong(&mut self, op: &ServerOp) -> bool {
+        match *op {
+            ServerOp::Pong if self.pings_out > 0 => {
+                self.pings_out -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+    /// read once from socket, closed connection is reported as error
+    fn read_to_parser(c: &mut Connection, parser: &mut Parser, buf: &mut [u8], from: &str) -> Result<()> {
+        let bytesread = Self::read_request(c, buf, from)?;
+        if bytesread == 0 {
+            return Err(c.lost(ErrorKind::UnexpectedEof, "connection closed by server", from));
+        }
+        parser.feed(&buf[0..bytesread]);
+        Ok(())
     }
     /// unsubscribe specified subscription ID.
     ///
@@ -429,61 +1274,442 @@ impl NatsClient {
         self.unsubscribe_internal(sid, Some(unsubscribe_after))
     }
     fn unsubscribe_internal(&mut self, sid: u64, unsubscribe_after: Option<i32>) -> Result<()> {
-        let datastr = match unsubscribe_after {
-            Some(v) => format!("UNSUB {} {}\r\n", sid, v),
-            None => format!("UNSUB {}\r\n", sid),
+        self.check_open()?;
+        let mut buf = Vec::new();
+        // server unsubscribes immediately if max is not positive
+        let max = unsubscribe_after.and_then(|v| if v > 0 { Some(v as u64) } else { None });
+        protocol::encode_unsub(sid, max, &mut buf);
+        // subscription is updated first, so that resubscribe after reconnect applies UNSUB
+        match unsubscribe_after {
+            Some(v) if v > 0 => {
+                let done = match self.subscriptions.get_mut(&sid) {
+                    Some(sub) => {
+                        sub.max_messages = Some(v as u64);
+                        sub.delivered >= v as u64
+                    }
+                    None => false,
+                };
+                if done {
+                    self.subscriptions.remove(&sid);
+                }
+            }
+            _ => {
+                self.subscriptions.remove(&sid);
+            }
+        }
+        self.write_or_recover(&buf, "NatsClient::unsubscribe", false)
+    }
+    fn count_delivered(&mut self, sid: u64) {
+        let done = match self.subscriptions.get_mut(&sid) {
+            Some(sub) => {
+                sub.delivered += 1;
+                sub.max_messages.is_some_and(|max| sub.delivered >= max)
+            }
+            None => false,
         };
-        Self::write_request(&mut self.tcp_client,
-                            datastr.as_bytes(),
-                            "NatsClient::unsubscribe")?;
-        Ok(())
+        if done {
+            self.subscriptions.remove(&sid);
+        }
     }
-    /// set read timeout for wait_message
-    pub fn set_read_timeout(&mut self, timeout: Option<time::Duration>) -> Result<()> {
-        Self::set_read_timeout_internal(&mut self.tcp_client, timeout)
+    /// correlate asynchronous server error to subscriptions and notify it to handler
+    fn handle_server_error(&mut self, e: &NatsServerError) {
+        let mut sids: Vec<u64> = Vec::new();
+        if let Some(PermissionViolation::Subscription { ref subject, ref queue }) =
+            e.permission_violation {
+            for (sid, sub) in self.subscriptions.iter_mut() {
+                if sub.error.is_none() && &sub.subject == subject && &sub.queue == queue {
+                    sub.error = Some(e.clone());
+                    sids.push(*sid);
+                }
+            }
+            sids.sort();
+            warn!("subscription denied by server({}): {:?}", subject, sids);
+        }
+        if let Some(ref mut handler) = self.handlers.async_error {
+            handler(e, &sids);
+        }
     }
-    fn write_request(c: &mut Write, data: &[u8], from: &str) -> Result<usize> {
-        match c.write(data) {
-            Ok(v) => Ok(v),
-            Err(e) => Err(NatsError::ConnectionError(e, from.to_owned())),
+    /// returns error if subscription was rejected by server(e.g. permissions violation).
+    ///
+    /// server sends the error asynchronously,
+    /// so it is set after wait_message returns NatsError::ServerError.
+    pub fn subscription_error(&self, sid: u64) -> Option<&NatsServerError> {
+        self.subscriptions.get(&sid).and_then(|sub| sub.error.as_ref())
+    }
+    /// set handler for "-ERR" which is sent from server asynchronously.
+    ///
+    /// second argument is sids of subscriptions affected by the error(empty if no subscription is related).
+    /// handler is called before wait_message returns NatsError::ServerError.
+    /// this replaces the handler registered by ConnectOption::set_async_error_handler.
+    pub fn set_async_error_handler<F>(&mut self, handler: F)
+        where F: FnMut(&NatsServerError, &[u64]) + Send + 'static
+    {
+        self.handlers.async_error = Some(Box::new(handler));
+    }
+    /// send PING and wait for PONG, so that all publishes sent before are processed by server.
+    ///
+    /// messages received while waiting are returned by following wait_message.
+    /// if connection is lost, the error is returned after reconnecting(if enabled),
+    /// because publishes sent to old connection may not be processed.
+    pub fn flush(&mut self, timeout: time::Duration) -> Result<()> {
+        self.check_open()?;
+        let deadline = time::Instant::now() + timeout;
+        self.flush_internal(deadline, "NatsClient::flush", Some)
+    }
+    /// drain the connection and close it.
+    ///
+    /// all subscriptions are unsubscribed, then messages already sent by server
+    /// (including ones not yet returned by wait_message) are passed to handler,
+    /// and publishes are flushed. late replies to request inbox are discarded.
+    /// Ok is returned when draining is completed, NatsError::Timeout if it did not complete within timeout.
+    /// connection is closed in both cases.
+    pub fn drain<F>(&mut self, timeout: time::Duration, mut handler: F) -> Result<()>
+        where F: FnMut(NatsMessage)
+    {
+        self.check_open()?;
+        self.state = ConnectionState::Draining;
+        let deadline = time::Instant::now() + timeout;
+        let mut sids: Vec<u64> = self.subscriptions.keys().cloned().collect();
+        sids.sort();
+        let mut ret = Ok(());
+        for sid in sids {
+            ret = self.unsubscribe(sid);
+            if ret.is_err() {
+                break;
+            }
         }
+        if ret.is_ok() {
+            while let Some(msg) = self.pop_pending_message() {
+                handler(msg);
+            }
+            // late replies to request inbox are discarded like wait_message
+            let response_sid = self.response_subscription.as_ref().map(|v| v.0);
+            ret = self.flush_internal(deadline, "NatsClient::drain", |msg| {
+                if Some(msg.sid) == response_sid {
+                    debug!("discard reply which nobody waits: {}", msg.subject);
+                } else {
+                    handler(msg);
+                }
+                None
+            });
+        }
+        self.tcp_client.shutdown().unwrap_or_default();
+        self.set_closed();
+        ret
     }
-    fn read_request(c: &mut Read, buf: &mut [u8], from: &str) -> Result<usize> {
-        match c.read(buf) {
-            Ok(v) => Ok(v),
-            Err(e) => Err(NatsError::ConnectionError(e, from.to_owned())),
+    /// drain the subscription.
+    ///
+    /// subscription is unsubscribed, then its messages already sent by server are passed to handler.
+    /// messages of other subscriptions are returned by following wait_message.
+    /// Ok is returned when draining is completed, NatsError::Timeout if it did not complete within timeout.
+    pub fn drain_subscription<F>(&mut self, sid: u64, timeout: time::Duration, mut handler: F) -> Result<()>
+        where F: FnMut(NatsMessage)
+    {
+        self.check_open()?;
+        let deadline = time::Instant::now() + timeout;
+        self.unsubscribe(sid)?;
+        let (drained, others): (VecDeque<NatsMessage>, VecDeque<NatsMessage>) =
+            self.pending_messages.drain(..).partition(|msg| msg.sid == sid);
+        self.pending_messages = others;
+        for msg in drained {
+            handler(msg);
+        }
+        self.flush_internal(deadline, "NatsClient::drain_subscription", |msg| {
+            if msg.sid == sid {
+                handler(msg);
+                None
+            } else {
+                Some(msg)
+            }
+        })
+    }
+    /// send PING and process responses until PONG.
+    ///
+    /// messages are passed to handler, message returned from handler is queued for wait_message.
+    fn flush_internal<F>(&mut self, deadline: time::Instant, from: &str, handler: F) -> Result<()>
+        where F: FnMut(NatsMessage) -> Option<NatsMessage>
+    {
+        let ret = self.flush_until_pong(deadline, from, handler);
+        let ret = self.restore_read_timeout(ret);
+        self.fail_with(ret)
+    }
+    fn flush_until_pong<F>(&mut self, deadline: time::Instant, from: &str, mut handler: F) -> Result<()>
+        where F: FnMut(NatsMessage) -> Option<NatsMessage>
+    {
+        Self::write_request(&mut self.tcp_client, &protocol::ClientOp::Ping.encode(), from)?;
+        loop {
+            match self.receive_until(deadline, from) {
+                Ok(NatsResponse::Pong) => return Ok(()),
+                Ok(NatsResponse::Msg(msg)) => {
+                    if let Some(msg) = handler(msg) {
+                        self.pending_messages.push_back(msg);
+                    }
+                }
+                Ok(_) => {}
+                // already notified to async error handler
+                Err(NatsError::ServerError(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    /// receive next response until deadline.
+    ///
+    /// PING from server is answered and PONG for keepalive is skipped.
+    /// read timeout must be restored by restore_read_timeout.
+    fn receive_until(&mut self, deadline: time::Instant, from: &str) -> Result<NatsResponse> {
+        loop {
+            let op = match self.parser.next_op()? {
+                Some(ref op) if self.is_keepalive_pong(op) => continue,
+                Some(op) => op,
+                None => {
+                    let now = time::Instant::now();
+                    if now >= deadline {
+                        return Err(NatsError::timeout(from));
+                    }
+                    Self::set_read_timeout_internal(&mut self.tcp_client, Some(deadline - now))?;
+                    Self::read_to_parser(&mut self.tcp_client,
+                                         &mut self.parser,
+                                         &mut self.read_buffer,
+                                         from)?;
+                    continue;
+                }
+            };
+            match self.handle_op(op)? {
+                NatsResponse::Ping => {
+                    Self::write_request(&mut self.tcp_client,
+                                        &protocol::ClientOp::Pong.encode(),
+                                        from)?;
+                }
+                resp => return Ok(resp),
+            }
         }
     }
-    fn find_crlf(dat: &[u8]) -> Option<usize> {
-        // debug!("finding crlf(buflen={:?})", dat.len());
-        if dat.len() < 2 {
-            debug!("buffer to short");
-            return None;
+    fn restore_read_timeout<R>(&mut self, ret: Result<R>) -> Result<R> {
+        let timeout = self.read_timeout;
+        let restored = Self::set_read_timeout_internal(&mut self.tcp_client, timeout);
+        ret.and_then(|v| restored.map(|_| v))
+    }
+    /// wait for a message which satisfies pred until deadline.
+    ///
+    /// other messages are returned by following wait_message.
+    pub(crate) fn wait_for_message<F>(&mut self,
+                                      deadline: time::Instant,
+                                      from: &str,
+                                      mut pred: F)
+                                      -> Result<NatsMessage>
+        where F: FnMut(&NatsMessage) -> bool
+    {
+        self.check_open()?;
+        if let Some(index) = self.pending_messages.iter().position(&mut pred) {
+            if let Some(msg) = self.pending_messages.remove(index) {
+                self.awaited_replies.remove(&msg.subject);
+                return Ok(msg);
+            }
+        }
+        let ret = self.receive_message_until(deadline, from, pred);
+        let ret = self.restore_read_timeout(ret);
+        if let Ok(ref msg) = ret {
+            self.awaited_replies.remove(&msg.subject);
         }
-        for i in 0..dat.len() - 1 {
-            if dat[i] == 0x0d && dat[i + 1] == 0x0a {
-                // debug!("found crlf:{}", i);
-                return Some(i);
+        self.fail_with(ret)
+    }
+    /// stop waiting the reply, it is discarded if it arrives later
+    pub(crate) fn forget_reply(&mut self, reply: &str) {
+        self.awaited_replies.remove(reply);
+    }
+    fn receive_message_until<F>(&mut self, deadline: time::Instant, from: &str, mut pred: F) -> Result<NatsMessage>
+        where F: FnMut(&NatsMessage) -> bool
+    {
+        loop {
+            if let NatsResponse::Msg(msg) = self.receive_until(deadline, from)? {
+                if pred(&msg) {
+                    return Ok(msg);
+                }
+                self.pending_messages.push_back(msg);
             }
         }
-        debug!("no crlf found");
-        return None;
     }
-    fn consume_verbose_response(&mut self) -> Result<()> {
-        // debug!("receiving verbose message");
-        let mut buf = [0u8; 512];
-        let bytesread = match self.tcp_client.read(&mut buf) {
-            Ok(v) => v,
+    /// returns unique subject for receiving replies("_INBOX.<random>")
+    pub fn new_inbox(&self) -> String {
+        format!("_INBOX.{}", unique_token())
+    }
+    /// publish request and wait for its reply.
+    ///
+    /// replies are received by a subscription to "_INBOX.<random>.*" which is created on first request.
+    /// NatsError::NoResponders is returned if server notifies that nobody subscribes subject.
+    /// messages of other subscriptions received while waiting are returned by following wait_message.
+    /// if connection is lost while waiting, the error is returned after reconnecting(if enabled).
+    pub fn request(&mut self, subject: &str, data: &[u8], timeout: time::Duration) -> Result<NatsMessage> {
+        self.request_internal(subject, None, data, timeout)
+    }
+    /// request with value serialized as JSON and deserialize its reply from JSON
+    pub fn request_json<T, R>(&mut self, subject: &str, value: &T, timeout: time::Duration) -> Result<R>
+        where T: Serialize,
+              R: DeserializeOwned
+    {
+        let data = serde_json::to_vec(value).map_err(NatsError::JsonError)?;
+        self.request_internal(subject, None, &data, timeout)?.json()
+    }
+    /// publish message whose reply subject is unique subject of response inbox.
+    ///
+    /// returns sid of response inbox and reply subject, reply is received by wait_for_message.
+    /// the reply is kept from wait_message until it is received or forget_reply is called.
+    pub(crate) fn publish_request(&mut self,
+                                  subject: &str,
+                                  headers: Option<&Headers>,
+                                  data: &[u8])
+                                  -> Result<(u64, String)> {
+        self.check_open()?;
+        let (sid, prefix) = match self.response_subscription {
+            Some((sid, ref prefix)) if self.subscriptions.contains_key(&sid) => (sid, prefix.clone()),
+            _ => {
+                let prefix = format!("{}.", self.new_inbox());
+                let sid = self.subscribe(&format!("{}*", prefix), None)?;
+                self.response_subscription = Some((sid, prefix.clone()));
+                (sid, prefix)
+            }
+        };
+        self.response_count += 1;
+        let reply = format!("{}{}", prefix, self.response_count);
+        self.publish_internal(subject, Some(&reply), headers, data)?;
+        self.awaited_replies.insert(reply.clone());
+        Ok((sid, reply))
+    }
+    /// publish request with headers and wait for its reply
+    pub fn request_with_headers(&mut self,
+                                subject: &str,
+                                headers: &Headers,
+                                data: &[u8],
+                                timeout: time::Duration)
+                                -> Result<NatsMessage> {
+        self.request_internal(subject, Some(headers), data, timeout)
+    }
+    fn request_internal(&mut self,
+                        subject: &str,
+                        headers: Option<&Headers>,
+                        data: &[u8],
+                        timeout: time::Duration)
+                        -> Result<NatsMessage> {
+        let deadline = time::Instant::now() + timeout;
+        let (sid, reply) = self.publish_request(subject, headers, data)?;
+        let ret = self.wait_for_message(deadline, "NatsClient::request", |msg| {
+            msg.sid == sid && msg.subject == reply
+        });
+        let msg = match ret {
+            Ok(msg) => msg,
             Err(e) => {
-                return Err(NatsError::ConnectionError(e, "NatsClient::publish::read_ok".to_owned()))
+                self.forget_reply(&reply);
+                return Err(e);
             }
         };
-        self.receive_buffer
-            .append(&mut buf[0..bytesread].to_vec());
-        // debug!("receiving verbose message done:{}", bytesread);
+        if msg.is_no_responders() {
+            return Err(NatsError::NoResponders(subject.to_owned()));
+        }
+        Ok(msg)
+    }
+    /// set handler which is called when server enters lame duck mode(INFO with ldm=true).
+    ///
+    /// handler is called before wait_message returns the INFO.
+    /// if reconnect is enabled, client moves to another server in the pool
+    /// after messages already received from the server are returned by wait_message.
+    /// subscriptions are sent to new server, then they are unsubscribed from old server
+    /// and messages which old server has sent until PONG are returned by wait_message,
+    /// so messages published while moving may be received twice.
+    /// this replaces the handler registered by ConnectOption::set_lame_duck_handler.
+    pub fn set_lame_duck_handler<F>(&mut self, handler: F)
+        where F: FnMut(&ServerInfo) + Send + 'static
+    {
+        self.handlers.lame_duck = Some(Box::new(handler));
+    }
+    /// enable or disable reconnecting to servers in the pool(default: disabled)
+    ///
+    /// pool consists of the server passed to constructor and connect_urls notified by server.
+    pub fn set_reconnect(&mut self, enabled: bool) {
+        self.reconnect = if enabled {
+            Some(self.reconnect.unwrap_or_default())
+        } else {
+            None
+        };
+    }
+    /// known servers("host:port"), the first one is the server passed to constructor
+    pub fn servers(&self) -> &[String] {
+        &self.servers
+    }
+    /// current state of connection
+    pub fn status(&self) -> ConnectionState {
+        self.state
+    }
+    /// close the connection.
+    ///
+    /// unlike drop, error on closing socket is returned.
+    /// messages which are not returned by wait_message are discarded(use drain to receive them).
+    pub fn close(&mut self) -> Result<()> {
+        self.check_open()?;
+        let ret = match self.tcp_client.shutdown() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(NatsError::from_io(e, "NatsClient::close", &self.tcp_client.server)),
+        };
+        self.set_closed();
+        ret
+    }
+    fn set_closed(&mut self) {
+        if self.state == ConnectionState::Closed {
+            return;
+        }
+        self.state = ConnectionState::Closed;
+        if let Some(ref mut handler) = self.handlers.closed {
+            handler();
+        }
+    }
+    fn check_open(&self) -> Result<()> {
+        if self.state == ConnectionState::Closed {
+            return Err(NatsError::ConnectionClosed);
+        }
+        Ok(())
+    }
+    /// set read timeout for wait_message
+    pub fn set_read_timeout(&mut self, timeout: Option<time::Duration>) -> Result<()> {
+        self.check_open()?;
+        Self::set_read_timeout_internal(&mut self.tcp_client, timeout)?;
+        self.read_timeout = timeout;
         Ok(())
     }
+    fn write_request(c: &mut Connection, data: &[u8], from: &str) -> Result<usize> {
+        match c.stream.write_all(data) {
+            Ok(_) => Ok(data.len()),
+            Err(e) => Err(NatsError::from_io(e, from, &c.server)),
+        }
+    }
+    fn read_request(c: &mut Connection, buf: &mut [u8], from: &str) -> Result<usize> {
+        match c.stream.read(buf) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(NatsError::from_io(e, from, &c.server)),
+        }
+    }
+    fn consume_verbose_response(&mut self) -> Result<()> {
+        // debug!("receiving verbose message");
+        Self::read_to_parser(&mut self.tcp_client,
+                             &mut self.parser,
+                             &mut self.read_buffer,
+                             "NatsClient::publish::read_ok")
+    }
+    /// consume "+OK" of publish or subscribe.
+    /// operation has been applied to new connection if client reconnects.
+    fn consume_verbose_or_recover(&mut self) -> Result<()> {
+        match self.consume_verbose_response() {
+            Err(e @ NatsError::ConnectionError { .. }) => {
+                if self.recover_connection(&e) {
+                    Ok(())
+                } else {
+                    Err(e)
+                }
+            }
+            ret => ret,
+        }
+    }
     /// getter for ServerInfo coming from server
+    ///
+    /// it is updated when wait_message receives asynchronous INFO.
     pub fn get_server_info(&self) -> &ServerInfo {
         &self.server_info
     }
@@ -491,9 +1717,10 @@ impl NatsClient {
 
 impl Drop for NatsClient {
     fn drop(&mut self) {
-        self.tcp_client
-            .shutdown(net::Shutdown::Both)
-            .unwrap_or_default();
+        if self.state == ConnectionState::Closed {
+            return;
+        }
+        self.tcp_client.shutdown().unwrap_or_default();
     }
 }
 
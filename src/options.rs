@@ -0,0 +1,229 @@
+//! fluent builder of connection settings
+//!
+//! ```
+//! extern crate simple_nats_client;
+//! use std::time::Duration;
+//! use simple_nats_client::options::Options;
+//! let c = Options::new()
+//!     .server("127.0.0.1:4222")
+//!     .name("myapp")
+//!     .read_timeout(Duration::from_secs(10))
+//!     .max_reconnects(10)
+//!     .connect();
+//! ```
+use std::time::Duration;
+
+use connect_option::ConnectOption;
+use nats_client::{NatsClient, NatsError, NatsServerError};
+use server_info::ServerInfo;
+
+type Result<T> = ::std::result::Result<T, NatsError>;
+
+/// server which is used if no server is added to Options
+pub const DEFAULT_SERVER: &str = "127.0.0.1:4222";
+/// default size of buffer for reading socket
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 512;
+/// default number of PINGs which can be unanswered before connection is regarded as stale
+pub const DEFAULT_MAX_PINGS_OUT: u32 = 2;
+
+/// how client reconnects when connection is lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// number of trials over all servers in the pool, 0 disables reconnect
+    pub max_reconnects: usize,
+    /// wait between trials, it is not applied before the first trial
+    pub reconnect_wait: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_reconnects: 60,
+            reconnect_wait: Duration::from_secs(2),
+        }
+    }
+}
+
+/// connection settings, NatsClient is created by connect
+#[derive(Clone)]
+pub struct Options {
+    pub(crate) servers: Vec<String>,
+    pub(crate) connect_option: ConnectOption,
+    pub(crate) tls_required: bool,
+    pub(crate) read_timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) ping_interval: Option<Duration>,
+    pub(crate) max_pings_out: u32,
+    pub(crate) reconnect: Option<ReconnectPolicy>,
+    pub(crate) read_buffer_size: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
+    /// default settings, +OK is not requested(verbose=false) and reconnect is disabled
+    pub fn new() -> Options {
+        Options {
+            servers: Vec::new(),
+            connect_option: ConnectOption::new(),
+            tls_required: false,
+            read_timeout: None,
+            connect_timeout: None,
+            ping_interval: None,
+            max_pings_out: DEFAULT_MAX_PINGS_OUT,
+            reconnect: None,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+        }
+    }
+    /// add server("host:port") to the pool, servers are tried in added order
+    pub fn server(mut self, server: &str) -> Self {
+        self.servers.push(server.to_owned());
+        self
+    }
+    /// add servers("host:port") to the pool
+    pub fn servers<S: AsRef<str>>(mut self, servers: &[S]) -> Self {
+        self.servers.extend(servers.iter().map(|v| v.as_ref().to_owned()));
+        self
+    }
+    /// authenticate with user and password
+    pub fn user_and_password(mut self, user: &str, pass: &str) -> Self {
+        self.connect_option.user = user.to_owned();
+        self.connect_option.pass = pass.to_owned();
+        self
+    }
+    /// authenticate with token
+    pub fn token(mut self, token: &str) -> Self {
+        self.connect_option.auth_token = token.to_owned();
+        self
+    }
+    /// require TLS connection.
+    ///
+    /// TLS is not supported by this client.
+    /// connect fails with NatsError::ConnectionError(ErrorKind::Unsupported)
+    /// if this is true or server requires TLS.
+    pub fn tls_required(mut self, required: bool) -> Self {
+        self.tls_required = required;
+        self.connect_option.ssl_required = required;
+        self
+    }
+    /// read timeout of wait_message
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+    /// timeout of connecting each address and receiving INFO(default: OS default of connect)
+    ///
+    /// all addresses which server is resolved to are tried in order.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+    /// send PING when nothing is received in wait_message for the interval
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = Some(interval);
+        self
+    }
+    /// number of unanswered PINGs before connection is regarded as lost
+    pub fn max_pings_out(mut self, max: u32) -> Self {
+        self.max_pings_out = max;
+        self
+    }
+    /// enable reconnect with the policy
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+    /// enable reconnect with max_reconnects trials over the pool, 0 disables reconnect
+    pub fn max_reconnects(mut self, max: usize) -> Self {
+        let mut policy = self.reconnect.unwrap_or_default();
+        policy.max_reconnects = max;
+        self.reconnect = Some(policy);
+        self
+    }
+    /// enable reconnect waiting the duration between trials over the pool
+    pub fn reconnect_wait(mut self, wait: Duration) -> Self {
+        let mut policy = self.reconnect.unwrap_or_default();
+        policy.reconnect_wait = wait;
+        self.reconnect = Some(policy);
+        self
+    }
+    /// disable reconnect
+    pub fn no_reconnect(mut self) -> Self {
+        self.reconnect = None;
+        self
+    }
+    /// size of buffer for reading socket
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        self.read_buffer_size = size.max(1);
+        self
+    }
+    /// client name which is shown in server monitoring
+    pub fn name(mut self, name: &str) -> Self {
+        self.connect_option.name = name.to_owned();
+        self
+    }
+    /// receive messages published by this connection(default: true)
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.connect_option.echo = echo;
+        self
+    }
+    /// request "+OK" for each operation(default: false)
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.connect_option.verbose = verbose;
+        self
+    }
+    /// see ConnectOption::set_disconnected_handler
+    pub fn disconnected_handler<F>(mut self, handler: F) -> Self
+        where F: Fn(&str, &NatsError) + Send + Sync + 'static
+    {
+        self.connect_option.set_disconnected_handler(handler);
+        self
+    }
+    /// see ConnectOption::set_reconnected_handler
+    pub fn reconnected_handler<F>(mut self, handler: F) -> Self
+        where F: Fn(&str) + Send + Sync + 'static
+    {
+        self.connect_option.set_reconnected_handler(handler);
+        self
+    }
+    /// see ConnectOption::set_closed_handler
+    pub fn closed_handler<F>(mut self, handler: F) -> Self
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.connect_option.set_closed_handler(handler);
+        self
+    }
+    /// see ConnectOption::set_discovered_servers_handler
+    pub fn discovered_servers_handler<F>(mut self, handler: F) -> Self
+        where F: Fn(&[String]) + Send + Sync + 'static
+    {
+        self.connect_option.set_discovered_servers_handler(handler);
+        self
+    }
+    /// see ConnectOption::set_lame_duck_handler
+    pub fn lame_duck_handler<F>(mut self, handler: F) -> Self
+        where F: Fn(&ServerInfo) + Send + Sync + 'static
+    {
+        self.connect_option.set_lame_duck_handler(handler);
+        self
+    }
+    /// see ConnectOption::set_async_error_handler
+    pub fn async_error_handler<F>(mut self, handler: F) -> Self
+        where F: Fn(&NatsServerError, &[u64]) + Send + Sync + 'static
+    {
+        self.connect_option.set_async_error_handler(handler);
+        self
+    }
+    /// CONNECT parameters which will be sent
+    pub fn connect_option(&self) -> &ConnectOption {
+        &self.connect_option
+    }
+    /// connect to the first available server in the pool
+    pub fn connect(&self) -> Result<NatsClient> {
+        NatsClient::connect_with_options(self)
+    }
+}
@@ -0,0 +1,439 @@
+//! NATS wire protocol encoder and parser which are independent from socket
+//!
+//! received bytes are passed by `Parser::feed`, and complete operations are taken by
+//! `Parser::next_op`. malformed input never panics, it is reported as NatsError.
+//!
+//! client operations are written by `encode_*` functions.
+//! `ClientOp::decode` is the reverse of them, it is useful for testing.
+
+use std::str;
+use std::str::FromStr;
+
+use nats_client::{Headers, NatsError, NatsMessage, NatsServerError, ServerInfo};
+
+type Result<T> = ::std::result::Result<T, NatsError>;
+
+/// limit of control line(e.g. "MSG subject 1 10") length
+pub const MAX_CONTROL_LINE_SIZE: usize = 1024 * 1024;
+/// largest message which is accepted(max_payload of server cannot exceed 64MB)
+pub const MAX_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// operation sent from server
+#[derive(Debug)]
+pub enum ServerOp {
+    Info(Box<ServerInfo>),
+    Msg(NatsMessage),
+    Ok,
+    Err(NatsServerError),
+    Ping,
+    Pong,
+}
+
+/// operation sent from client
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientOp {
+    /// JSON of ConnectOption
+    Connect(String),
+    Pub {
+        subject: String,
+        reply: Option<String>,
+        payload: Vec<u8>,
+    },
+    Hpub {
+        subject: String,
+        reply: Option<String>,
+        headers: Headers,
+        payload: Vec<u8>,
+    },
+    Sub {
+        subject: String,
+        queue: Option<String>,
+        sid: u64,
+    },
+    Unsub { sid: u64, max: Option<u64> },
+    Ping,
+    Pong,
+}
+
+impl ClientOp {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+        match *self {
+            ClientOp::Connect(ref json) => encode_connect(json, &mut ret),
+            ClientOp::Pub { ref subject, ref reply, ref payload } => {
+                encode_pub(subject, reply.as_deref(), None, payload, &mut ret)
+            }
+            ClientOp::Hpub { ref subject, ref reply, ref headers, ref payload } => {
+                encode_pub(subject, reply.as_deref(), Some(headers), payload, &mut ret)
+            }
+            ClientOp::Sub { ref subject, ref queue, sid } => {
+                encode_sub(subject, queue.as_deref(), sid, &mut ret)
+            }
+            ClientOp::Unsub { sid, max } => encode_unsub(sid, max, &mut ret),
+            ClientOp::Ping => ret.extend_from_slice(b"PING\r\n"),
+            ClientOp::Pong => ret.extend_from_slice(b"PONG\r\n"),
+        }
+        ret
+    }
+    /// decode one operation from head of data.
+    /// returns operation and consumed bytes, or None if data is incomplete.
+    pub fn decode(data: &[u8]) -> Result<Option<(ClientOp, usize)>> {
+        let crlf_index = match find_crlf(data) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let line = match str::from_utf8(&data[..crlf_index]) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(NatsError::MessageParseError("client op".to_owned(), format!("{}", e)))
+            }
+        };
+        let consumed = crlf_index + 2;
+        let (op, rest) = match line.find([' ', '\t']) {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let op = match op.to_uppercase().as_str() {
+            "CONNECT" => ClientOp::Connect(rest.to_owned()),
+            "PING" => ClientOp::Ping,
+            "PONG" => ClientOp::Pong,
+            "SUB" => {
+                let (subject, queue, sidstr) = match args.len() {
+                    2 => (args[0], None, args[1]),
+                    3 => (args[0], Some(args[1].to_owned()), args[2]),
+                    _ => return Err(invalid_arguments(&args)),
+                };
+                ClientOp::Sub {
+                    subject: subject.to_owned(),
+                    queue,
+                    sid: parse_size("sid", sidstr)?,
+                }
+            }
+            "UNSUB" => {
+                let max = match args.len() {
+                    1 => None,
+                    2 => Some(parse_size("max", args[1])?),
+                    _ => return Err(invalid_arguments(&args)),
+                };
+                ClientOp::Unsub {
+                    sid: parse_size("sid", args[0])?,
+                    max,
+                }
+            }
+            "PUB" => return decode_pub(&args, false, &data[consumed..], consumed),
+            "HPUB" => return decode_pub(&args, true, &data[consumed..], consumed),
+            _ => return Err(NatsError::UnknownResponse(line.to_owned())),
+        };
+        Ok(Some((op, consumed)))
+    }
+}
+
+/// decode PUB/HPUB, body is data after control line
+fn decode_pub(args: &[&str],
+              with_headers: bool,
+              body: &[u8],
+              line_size: usize)
+              -> Result<Option<(ClientOp, usize)>> {
+    let (subject, reply, hdrsizestr, sizestr) = match (args.len(), with_headers) {
+        (2, false) => (args[0], None, None, args[1]),
+        (3, false) => (args[0], Some(args[1]), None, args[2]),
+        (3, true) => (args[0], None, Some(args[1]), args[2]),
+        (4, true) => (args[0], Some(args[1]), Some(args[2]), args[3]),
+        _ => return Err(invalid_arguments(args)),
+    };
+    let size = parse_size::<usize>("size", sizestr)?;
+    if size > MAX_PAYLOAD_SIZE {
+        return Err(NatsError::MessageParseError("size too large".to_owned(), sizestr.to_owned()));
+    }
+    // payload + CRLF
+    if body.len() < size + 2 {
+        return Ok(None);
+    }
+    if &body[size..size + 2] != b"\r\n" {
+        return Err(NatsError::MessageParseError("payload".to_owned(),
+                                                "payload is not terminated by CRLF".to_owned()));
+    }
+    let subject = subject.to_owned();
+    let reply = reply.map(|v| v.to_owned());
+    let op = match hdrsizestr {
+        Some(v) => {
+            let hdrsize = parse_size::<usize>("hdrsize", v)?;
+            if hdrsize > size {
+                return Err(NatsError::MessageParseError("hdrsize larger than size".to_owned(),
+                                                        v.to_owned()));
+            }
+            ClientOp::Hpub {
+                subject,
+                reply,
+                headers: Headers::from_bytes(&body[..hdrsize])?,
+                payload: body[hdrsize..size].to_vec(),
+            }
+        }
+        None => {
+            ClientOp::Pub {
+                subject,
+                reply,
+                payload: body[..size].to_vec(),
+            }
+        }
+    };
+    Ok(Some((op, line_size + size + 2)))
+}
+
+fn invalid_arguments(args: &[&str]) -> NatsError {
+    NatsError::InvalidMessageArgument(args.iter().map(|&x| String::from(x)).collect())
+}
+
+/// write "CONNECT {json}"
+pub fn encode_connect(json: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(b"CONNECT ");
+    out.extend_from_slice(json.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+/// write PUB, or HPUB if headers is passed
+pub fn encode_pub(subject: &str,
+                  reply: Option<&str>,
+                  headers: Option<&Headers>,
+                  payload: &[u8],
+                  out: &mut Vec<u8>) {
+    let reply = match reply {
+        Some(v) => format!(" {}", v),
+        None => String::new(),
+    };
+    match headers {
+        Some(h) => {
+            let header_bytes = h.to_bytes();
+            out.extend_from_slice(format!("HPUB {}{} {} {}\r\n",
+                                          subject,
+                                          reply,
+                                          header_bytes.len(),
+                                          header_bytes.len() + payload.len())
+                                          .as_bytes());
+            out.extend_from_slice(&header_bytes);
+        }
+        None => {
+            out.extend_from_slice(format!("PUB {}{} {}\r\n", subject, reply, payload.len())
+                                      .as_bytes())
+        }
+    }
+    out.extend_from_slice(payload);
+    out.extend_from_slice(b"\r\n");
+}
+
+/// write SUB
+pub fn encode_sub(subject: &str, queue: Option<&str>, sid: u64, out: &mut Vec<u8>) {
+    let line = match queue {
+        Some(v) => format!("SUB {} {} {}\r\n", subject, v, sid),
+        None => format!("SUB {} {}\r\n", subject, sid),
+    };
+    out.extend_from_slice(line.as_bytes());
+}
+
+/// write UNSUB, max is number of messages before auto unsubscribe
+pub fn encode_unsub(sid: u64, max: Option<u64>, out: &mut Vec<u8>) {
+    let line = match max {
+        Some(v) => format!("UNSUB {} {}\r\n", sid, v),
+        None => format!("UNSUB {}\r\n", sid),
+    };
+    out.extend_from_slice(line.as_bytes());
+}
+
+/// MSG/HMSG control line waiting for its payload
+#[derive(Debug)]
+struct MessageHeader {
+    subject: String,
+    sid: u64,
+    reply: Option<String>,
+    header_size: Option<usize>,
+    total_size: usize,
+}
+
+/// incremental parser for server operations
+#[derive(Debug)]
+pub struct Parser {
+    buffer: Vec<u8>,
+    pending: Option<MessageHeader>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            buffer: Vec::new(),
+            pending: None,
+        }
+    }
+    /// append received bytes
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+    /// number of bytes which are not consumed yet
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+    /// take next complete operation, returns None if more data is needed.
+    ///
+    /// the invalid part of input is discarded when error is returned,
+    /// so parsing can be continued(though stream may be out of sync).
+    pub fn next_op(&mut self) -> Result<Option<ServerOp>> {
+        if self.pending.is_none() {
+            let line = match self.take_line()? {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+            match Self::parse_line(&line)? {
+                LineResult::Op(op) => return Ok(Some(op)),
+                LineResult::Message(header) => self.pending = Some(header),
+            }
+        }
+        self.take_payload()
+    }
+    fn take_line(&mut self) -> Result<Option<String>> {
+        let crlf_index = match find_crlf(&self.buffer) {
+            Some(v) => v,
+            None => {
+                if self.buffer.len() > MAX_CONTROL_LINE_SIZE {
+                    self.buffer.clear();
+                    return Err(NatsError::MessageParseError("control line".to_owned(),
+                                                            "too long control line".to_owned()));
+                }
+                return Ok(None);
+            }
+        };
+        let rest = self.buffer.split_off(crlf_index + 2);
+        let mut line = ::std::mem::replace(&mut self.buffer, rest);
+        line.truncate(crlf_index);
+        match String::from_utf8(line) {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => Err(NatsError::EncodingError(e)),
+        }
+    }
+    fn parse_line(line: &str) -> Result<LineResult> {
+        let trimmed = line.trim_start();
+        let (op, rest) = match trimmed.find([' ', '\t']) {
+            Some(i) => (&trimmed[..i], trimmed[i + 1..].trim()),
+            None => (trimmed.trim_end(), ""),
+        };
+        let op = match op.to_uppercase().as_str() {
+            "+OK" => ServerOp::Ok,
+            "-ERR" => ServerOp::Err(NatsServerError::new(rest)),
+            "PING" => ServerOp::Ping,
+            "PONG" => ServerOp::Pong,
+            "INFO" => ServerOp::Info(Box::new(parse_server_info(rest)?)),
+            "MSG" => return Ok(LineResult::Message(parse_message_args(rest, false)?)),
+            "HMSG" => return Ok(LineResult::Message(parse_message_args(rest, true)?)),
+            _ => return Err(NatsError::UnknownResponse(line.to_owned())),
+        };
+        Ok(LineResult::Op(op))
+    }
+    fn take_payload(&mut self) -> Result<Option<ServerOp>> {
+        let total_size = match self.pending {
+            Some(ref v) => v.total_size,
+            None => return Ok(None),
+        };
+        // payload + CRLF
+        if self.buffer.len() < total_size + 2 {
+            return Ok(None);
+        }
+        let header = match self.pending.take() {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let rest = self.buffer.split_off(total_size + 2);
+        let mut data = ::std::mem::replace(&mut self.buffer, rest);
+        if &data[total_size..] != b"\r\n" {
+            return Err(NatsError::MessageParseError("payload".to_owned(),
+                                                    "payload is not terminated by CRLF"
+                                                        .to_owned()));
+        }
+        data.truncate(total_size);
+        let headers = match header.header_size {
+            Some(header_size) => {
+                let payload = data.split_off(header_size);
+                let h = Headers::from_bytes(&data)?;
+                data = payload;
+                Some(h)
+            }
+            None => None,
+        };
+        Ok(Some(ServerOp::Msg(NatsMessage {
+            sid: header.sid,
+            data,
+            subject: header.subject,
+            reply: header.reply,
+            headers,
+        })))
+    }
+}
+
+enum LineResult {
+    Op(ServerOp),
+    Message(MessageHeader),
+}
+
+/// parse JSON of "INFO {...}"
+pub fn parse_server_info(json: &str) -> Result<ServerInfo> {
+    match ::serde_json::from_str(json) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(NatsError::MessageParseError("INFO".to_owned(), format!("{}", e))),
+    }
+}
+
+/// parse arguments of "MSG <subject> <sid> [reply] <size>"
+/// or "HMSG <subject> <sid> [reply] <header size> <total size>"
+fn parse_message_args(args: &str, with_headers: bool) -> Result<MessageHeader> {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let (subject, sidstr, reply, hdrsizestr, msgsizestr) = match (args.len(), with_headers) {
+        (3, false) => (args[0], args[1], None, None, args[2]),
+        (4, false) => (args[0], args[1], Some(args[2]), None, args[3]),
+        (4, true) => (args[0], args[1], None, Some(args[2]), args[3]),
+        (5, true) => (args[0], args[1], Some(args[2]), Some(args[3]), args[4]),
+        _ => return Err(invalid_arguments(&args)),
+    };
+    let sid = parse_size::<u64>("sid", sidstr)?;
+    let total_size = parse_size::<usize>("msgsize", msgsizestr)?;
+    if total_size > MAX_PAYLOAD_SIZE {
+        return Err(NatsError::MessageParseError("msgsize too large".to_owned(),
+                                                msgsizestr.to_owned()));
+    }
+    let header_size = match hdrsizestr {
+        Some(v) => {
+            let hdrsize = parse_size::<usize>("hdrsize", v)?;
+            if hdrsize > total_size {
+                return Err(NatsError::MessageParseError("hdrsize larger than msgsize"
+                                                            .to_owned(),
+                                                        v.to_owned()));
+            }
+            Some(hdrsize)
+        }
+        None => None,
+    };
+    Ok(MessageHeader {
+        subject: subject.to_owned(),
+        sid,
+        reply: reply.map(|v| v.to_owned()),
+        header_size,
+        total_size,
+    })
+}
+
+/// parse unsigned number, negative values are rejected
+fn parse_size<T: FromStr>(name: &str, v: &str) -> Result<T>
+    where T::Err: ::std::fmt::Display
+{
+    match T::from_str(v) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(NatsError::MessageParseError(format!("{}:{}", name, e), v.to_owned())),
+    }
+}
+
+fn find_crlf(dat: &[u8]) -> Option<usize> {
+    dat.windows(2).position(|w| w == b"\r\n")
+}
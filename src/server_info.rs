@@ -1,14 +1,52 @@
+use std::collections::HashMap;
 
+use serde_json::Value;
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+/// INFO sent from server (see: https://docs.nats.io/reference/reference-protocols/nats-protocol#info)
+///
+/// fields which are missing in INFO have default value,
+/// fields which are not known by this library are kept in `extra`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub struct ServerInfo {
     pub server_id: String,
+    pub server_name: String,
     pub version: String,
     pub go: String,
+    pub git_commit: String,
     pub host: String,
-    pub port: i32,
+    pub port: u16,
+    /// protocol version, 1 or later supports echo=false and async INFO
+    pub proto: i32,
+    /// client id assigned by server
+    pub client_id: u64,
+    /// client address seen from server
+    pub client_ip: String,
+    /// server supports HPUB/HMSG
+    pub headers: bool,
     pub auth_required: bool,
+    /// legacy name of tls_required
     pub ssl_required: bool,
+    pub tls_required: bool,
+    pub tls_verify: bool,
+    pub tls_available: bool,
     pub max_payload: i64,
+    /// challenge for nkey authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// JetStream domain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// other servers in cluster("host:port")
+    pub connect_urls: Vec<String>,
+    pub ws_connect_urls: Vec<String>,
+    /// lame duck mode, server is going to shutdown
+    pub ldm: bool,
+    /// server has JetStream enabled
+    pub jetstream: bool,
+    /// fields which are not defined above
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
-
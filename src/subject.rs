@@ -0,0 +1,288 @@
+//! subject utilities: validation, wildcard matching and routing table
+//!
+//! subject is '.' separated tokens.
+//! `*` matches a single token and `>` matches one or more tail tokens,
+//! they are treated as wildcard only when they are entire token.
+
+use std::collections::HashMap;
+use std::str::Split;
+
+use nats_client::NatsError;
+
+type Result<T> = ::std::result::Result<T, NatsError>;
+
+/// single token wildcard
+pub const SINGLE_WILDCARD: &str = "*";
+/// tail wildcard, only allowed as last token
+pub const FULL_WILDCARD: &str = ">";
+
+/// validate subject for SUB, wildcards are allowed
+pub fn validate_subject(subject: &str) -> Result<()> {
+    validate_internal(subject, true)
+}
+
+/// validate subject for PUB and reply-to, wildcards are not allowed
+pub fn validate_publish_subject(subject: &str) -> Result<()> {
+    validate_internal(subject, false)
+}
+
+/// validate queue group name, it must be non empty and must not contain whitespace
+pub fn validate_queue_name(queue: &str) -> Result<()> {
+    if queue.is_empty() {
+        return Err(invalid(queue, "queue name is empty"));
+    }
+    if queue.chars().any(is_forbidden_char) {
+        return Err(invalid(queue, "queue name contains whitespace or control character"));
+    }
+    Ok(())
+}
+
+/// returns true if subject has `*` or `>` token
+pub fn has_wildcard(subject: &str) -> bool {
+    subject.split('.').any(|t| t == SINGLE_WILDCARD || t == FULL_WILDCARD)
+}
+
+/// iterate '.' separated tokens of subject
+///
+/// # Examples
+///
+/// ```
+/// use simple_nats_client::subject;
+/// let tokens: Vec<&str> = subject::tokens("orders.eu.created").collect();
+/// assert_eq!(vec!["orders", "eu", "created"], tokens);
+/// ```
+pub fn tokens(subject: &str) -> Split<'_, char> {
+    subject.split('.')
+}
+
+/// returns true if subject matches pattern which may contain wildcards
+///
+/// # Examples
+///
+/// ```
+/// use simple_nats_client::subject;
+/// assert!(subject::matches("orders.*.created", "orders.eu.created"));
+/// assert!(subject::matches("orders.>", "orders.eu.created"));
+/// assert!(!subject::matches("orders.>", "orders"));
+/// ```
+pub fn matches(pattern: &str, subject: &str) -> bool {
+    captures(pattern, subject).is_some()
+}
+
+/// extract values matched by wildcards in pattern order.
+///
+/// `*` captures single token, `>` captures the rest of subject(e.g. "b.c").
+/// returns None if subject does not match pattern.
+///
+/// # Examples
+///
+/// ```
+/// use simple_nats_client::subject;
+/// assert_eq!(Some(vec!["eu"]), subject::captures("orders.*.created", "orders.eu.created"));
+/// assert_eq!(Some(vec!["eu", "a.b"]), subject::captures("orders.*.>", "orders.eu.a.b"));
+/// assert_eq!(None, subject::captures("orders.*.created", "orders.eu.deleted"));
+/// ```
+pub fn captures<'a>(pattern: &str, subject: &'a str) -> Option<Vec<&'a str>> {
+    let mut ret = Vec::new();
+    let mut pattern_tokens = tokens(pattern);
+    let mut offset = 0;
+    let mut subject_tokens = tokens(subject);
+    loop {
+        match (pattern_tokens.next(), subject_tokens.next()) {
+            (None, None) => return Some(ret),
+            (Some(FULL_WILDCARD), Some(_)) => {
+                if pattern_tokens.next().is_some() {
+                    return None;
+                }
+                ret.push(&subject[offset..]);
+                return Some(ret);
+            }
+            (Some(p), Some(t)) => {
+                if p == SINGLE_WILDCARD {
+                    ret.push(t);
+                } else if p != t {
+                    return None;
+                }
+                offset += t.len() + 1;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// routing table from subject pattern to value
+///
+/// patterns are stored in token trie, so lookup cost depends on subject length
+/// rather than number of patterns.
+///
+/// # Examples
+///
+/// ```
+/// use simple_nats_client::subject::SubjectMap;
+/// let mut m = SubjectMap::new();
+/// m.insert("orders.*.created", 1).unwrap();
+/// m.insert("orders.>", 2).unwrap();
+/// m.insert("users.>", 3).unwrap();
+/// let mut found: Vec<i32> = m.matches("orders.eu.created").into_iter().cloned().collect();
+/// found.sort();
+/// assert_eq!(vec![1, 2], found);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SubjectMap<T> {
+    root: Node<T>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: Option<T>,
+    children: HashMap<String, Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Node<T> {
+        Node {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.value.is_none() && self.children.is_empty()
+    }
+    fn collect<'a>(&'a self, tokens: &[&str], ret: &mut Vec<&'a T>) {
+        let (token, rest) = match tokens.split_first() {
+            Some(v) => v,
+            None => {
+                ret.extend(self.value.iter());
+                return;
+            }
+        };
+        if let Some(node) = self.children.get(*token) {
+            node.collect(rest, ret);
+        }
+        // literal "*" or ">" token is already matched by the lookup above
+        if *token != SINGLE_WILDCARD {
+            if let Some(node) = self.children.get(SINGLE_WILDCARD) {
+                node.collect(rest, ret);
+            }
+        }
+        if *token != FULL_WILDCARD {
+            if let Some(node) = self.children.get(FULL_WILDCARD) {
+                ret.extend(node.value.iter());
+            }
+        }
+    }
+    fn remove(&mut self, tokens: &[&str]) -> Option<T> {
+        match tokens.split_first() {
+            None => self.value.take(),
+            Some((token, rest)) => {
+                let (ret, prune) = match self.children.get_mut(*token) {
+                    Some(node) => {
+                        let ret = node.remove(rest);
+                        (ret, node.is_empty())
+                    }
+                    None => return None,
+                };
+                if prune {
+                    self.children.remove(*token);
+                }
+                ret
+            }
+        }
+    }
+}
+
+impl<T> Default for SubjectMap<T> {
+    fn default() -> Self {
+        SubjectMap::new()
+    }
+}
+
+impl<T> SubjectMap<T> {
+    pub fn new() -> SubjectMap<T> {
+        SubjectMap {
+            root: Node::new(),
+            len: 0,
+        }
+    }
+    /// add pattern, previous value is returned if pattern already exists.
+    /// returns NatsError::InvalidSubject if pattern is invalid.
+    pub fn insert(&mut self, pattern: &str, value: T) -> Result<Option<T>> {
+        validate_subject(pattern)?;
+        let mut node = &mut self.root;
+        for token in tokens(pattern) {
+            node = node.children.entry(token.to_owned()).or_insert_with(Node::new);
+        }
+        let ret = node.value.replace(value);
+        if ret.is_none() {
+            self.len += 1;
+        }
+        Ok(ret)
+    }
+    /// remove pattern which was added by insert
+    pub fn remove(&mut self, pattern: &str) -> Option<T> {
+        let tokens: Vec<&str> = tokens(pattern).collect();
+        let ret = self.root.remove(&tokens);
+        if ret.is_some() {
+            self.len -= 1;
+        }
+        ret
+    }
+    /// get value of pattern itself(no wildcard matching)
+    pub fn get(&self, pattern: &str) -> Option<&T> {
+        let mut node = &self.root;
+        for token in tokens(pattern) {
+            node = node.children.get(token)?;
+        }
+        node.value.as_ref()
+    }
+    /// get values of all patterns which match subject
+    pub fn matches(&self, subject: &str) -> Vec<&T> {
+        let tokens: Vec<&str> = tokens(subject).collect();
+        let mut ret = Vec::new();
+        self.root.collect(&tokens, &mut ret);
+        ret
+    }
+    /// number of patterns
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+fn validate_internal(subject: &str, allow_wildcard: bool) -> Result<()> {
+    if subject.is_empty() {
+        return Err(invalid(subject, "subject is empty"));
+    }
+    if subject.chars().any(is_forbidden_char) {
+        return Err(invalid(subject, "subject contains whitespace or control character"));
+    }
+    let mut tokens = subject.split('.').peekable();
+    while let Some(token) = tokens.next() {
+        if token.is_empty() {
+            return Err(invalid(subject, "subject has empty token"));
+        }
+        if token == SINGLE_WILDCARD || token == FULL_WILDCARD {
+            if !allow_wildcard {
+                return Err(invalid(subject, "wildcard is not allowed"));
+            }
+            if token == FULL_WILDCARD && tokens.peek().is_some() {
+                return Err(invalid(subject, "'>' must be last token"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_forbidden_char(c: char) -> bool {
+    c.is_whitespace() || c.is_control()
+}
+
+fn invalid(subject: &str, reason: &'static str) -> NatsError {
+    NatsError::InvalidSubject {
+        subject: subject.to_owned(),
+        reason,
+    }
+}
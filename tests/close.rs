@@ -0,0 +1,57 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::{connect, MockServer};
+use simple_nats_client::nats_client::{ConnectionState, NatsError};
+
+#[test]
+pub fn operations_after_close() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = connect(server.port);
+    assert_eq!(ConnectionState::Connected, c.status());
+    c.close().unwrap();
+    assert_eq!(ConnectionState::Closed, c.status());
+    match c.publish("foo", None, b"x") {
+        Err(NatsError::ConnectionClosed) => {}
+        _ => panic!("ConnectionClosed expected"),
+    }
+    match c.subscribe("foo", None) {
+        Err(NatsError::ConnectionClosed) => {}
+        _ => panic!("ConnectionClosed expected"),
+    }
+    match c.wait_message() {
+        Err(NatsError::ConnectionClosed) => {}
+        _ => panic!("ConnectionClosed expected"),
+    }
+    match c.close() {
+        Err(NatsError::ConnectionClosed) => {}
+        _ => panic!("ConnectionClosed expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn closed_when_connection_lost() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+    });
+    let port = server.port;
+    let mut c = connect(port);
+    server.join();
+    match c.wait_message() {
+        Err(NatsError::ConnectionError { ref operation, ref server, .. }) => {
+            assert_eq!("NatsClient::wait_message", operation);
+            assert_eq!(&format!("127.0.0.1:{}", port), server);
+        }
+        _ => panic!("ConnectionError expected"),
+    }
+    assert_eq!(ConnectionState::Closed, c.status());
+    match c.wait_message() {
+        Err(NatsError::ConnectionClosed) => {}
+        _ => panic!("ConnectionClosed expected"),
+    }
+}
@@ -0,0 +1,200 @@
+// helper for tests which do not require running NATS server
+#![allow(dead_code)]
+
+extern crate serde_json;
+
+use simple_nats_client::connect_option::ConnectOption;
+use simple_nats_client::nats_client::NatsClient;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_INFO: &str = r#"INFO {"server_id":"mock","version":"2.10.0","go":"go1.21","host":"127.0.0.1","port":4222,"auth_required":false,"ssl_required":false,"max_payload":1048576}"#;
+
+/// connection accepted by MockServer
+pub struct MockConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl MockConnection {
+    /// read one protocol line from client without CRLF
+    pub fn read_line(&mut self) -> String {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        line.trim_end_matches("\r\n").to_owned()
+    }
+    /// read payload which follows PUB line
+    pub fn read_payload(&mut self, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len + 2];
+        ::std::io::Read::read_exact(&mut self.reader, &mut buf).unwrap();
+        buf.truncate(len);
+        buf
+    }
+    /// send raw data to client, CRLF is not appended
+    pub fn send(&mut self, data: &str) {
+        self.writer.write_all(data.as_bytes()).unwrap();
+    }
+    /// read "SUB subject [queue] sid" and return subject and sid
+    pub fn read_sub(&mut self) -> (String, u64) {
+        let line = self.read_line();
+        let args: Vec<&str> = line.split(' ').collect();
+        assert_eq!("SUB", args[0], "SUB expected: {}", line);
+        (args[1].to_owned(), args[args.len() - 1].parse().unwrap())
+    }
+    /// read PUB or HPUB
+    pub fn read_pub(&mut self) -> Published {
+        let line = self.read_line();
+        let args: Vec<&str> = line.split(' ').collect();
+        let (headers_len, total_len) = match args[0] {
+            "PUB" => (None, args[args.len() - 1].parse().unwrap()),
+            "HPUB" => {
+                (Some(args[args.len() - 2].parse::<usize>().unwrap()),
+                 args[args.len() - 1].parse::<usize>().unwrap())
+            }
+            _ => panic!("PUB expected: {}", line),
+        };
+        let fixed = if headers_len.is_some() { 4 } else { 3 };
+        let reply = if args.len() > fixed { Some(args[2].to_owned()) } else { None };
+        let mut payload = self.read_payload(total_len);
+        let headers = headers_len.map(|len| {
+            let rest = payload.split_off(len);
+            String::from_utf8(::std::mem::replace(&mut payload, rest)).unwrap()
+        });
+        Published {
+            subject: args[1].to_owned(),
+            reply,
+            headers,
+            payload,
+        }
+    }
+    /// send MSG, or HMSG if headers("NATS/1.0...\r\n\r\n") is passed
+    pub fn send_msg(&mut self, subject: &str, sid: u64, reply: Option<&str>, headers: Option<&str>, payload: &[u8]) {
+        let reply = reply.map(|v| format!(" {}", v)).unwrap_or_default();
+        let mut buf = match headers {
+            Some(h) => {
+                let mut buf = format!("HMSG {} {}{} {} {}\r\n", subject, sid, reply, h.len(), h.len() + payload.len())
+                    .into_bytes();
+                buf.extend_from_slice(h.as_bytes());
+                buf
+            }
+            None => format!("MSG {} {}{} {}\r\n", subject, sid, reply, payload.len()).into_bytes(),
+        };
+        buf.extend_from_slice(payload);
+        buf.extend_from_slice(b"\r\n");
+        self.writer.write_all(&buf).unwrap();
+    }
+}
+
+/// PUB or HPUB received from client
+#[derive(Debug)]
+pub struct Published {
+    pub subject: String,
+    pub reply: Option<String>,
+    /// raw header block of HPUB
+    pub headers: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+impl Published {
+    /// payload as JSON, null if payload is empty
+    pub fn body(&self) -> serde_json::Value {
+        if self.payload.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_slice(&self.payload).unwrap()
+        }
+    }
+}
+
+/// single connection NATS server emulator
+pub struct MockServer {
+    pub port: i32,
+    handle: thread::JoinHandle<()>,
+}
+
+impl MockServer {
+    /// start server which sends `info` line and then passes connection to `f`
+    pub fn start<F>(info: &str, f: F) -> MockServer
+        where F: FnOnce(&mut MockConnection) + Send + 'static
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port() as i32;
+        let info = format!("{}\r\n", info);
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut conn = MockConnection {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            };
+            conn.send(&info);
+            f(&mut conn);
+        });
+        MockServer {
+            port,
+            handle,
+        }
+    }
+    pub fn join(self) {
+        self.handle.join().unwrap();
+    }
+}
+
+/// connect to MockServer without verbose mode
+pub fn connect(port: i32) -> NatsClient {
+    let mut opt = ConnectOption::new();
+    opt.verbose = false;
+    NatsClient::new_with_option("127.0.0.1", port, Some(Duration::from_secs(5)), Some(&opt)).unwrap()
+}
+
+/// read CONNECT and SUB of response inbox, returns sid of inbox
+pub fn accept_client(conn: &mut MockConnection) -> u64 {
+    assert!(conn.read_line().starts_with("CONNECT "));
+    conn.read_sub().1
+}
+
+/// read request and send response, returns the request
+pub fn respond(conn: &mut MockConnection, sid: u64, subject: &str, response: &str) -> Published {
+    let req = conn.read_pub();
+    assert_eq!(subject, req.subject);
+    conn.send_msg(req.reply.as_ref().unwrap(), sid, None, None, response.as_bytes());
+    req
+}
+
+/// info of ordered consumer used by kv and object store
+pub fn consumer_info(stream: &str, name: &str, pending: u64) -> String {
+    format!(r#"{{"stream_name":"{}","name":"{}","created":"2024-01-01T00:00:00Z",
+        "config":{{"ack_policy":"none","deliver_policy":"all","idle_heartbeat":5000000000,"flow_control":true}},
+        "num_pending":{}}}"#,
+            stream,
+            name,
+            pending)
+}
+
+/// read SUB of deliver subject and creation of ordered consumer, returns (deliver, sid, config)
+pub fn accept_consumer(conn: &mut MockConnection,
+                       inbox_sid: u64,
+                       stream: &str,
+                       name: &str,
+                       pending: u64)
+                       -> (String, u64, serde_json::Value) {
+    let (deliver, sid) = conn.read_sub();
+    let req = respond(conn,
+                      inbox_sid,
+                      &format!("$JS.API.CONSUMER.CREATE.{}", stream),
+                      &consumer_info(stream, name, pending));
+    (deliver, sid, req.body()["config"].clone())
+}
+
+/// read UNSUB of deliver subject and deletion of consumer
+pub fn close_consumer(conn: &mut MockConnection, inbox_sid: u64, sid: u64, stream: &str, name: &str) {
+    assert_eq!(format!("UNSUB {}", sid), conn.read_line());
+    respond(conn,
+            inbox_sid,
+            &format!("$JS.API.CONSUMER.DELETE.{}.{}", stream, name),
+            r#"{"success":true}"#);
+}
@@ -0,0 +1,54 @@
+extern crate simple_nats_client;
+extern crate serde_json;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::connect_option::ConnectOption;
+use simple_nats_client::nats_client::{NatsClient, NatsError};
+use std::time::Duration;
+
+const PROTO1_INFO: &str = r#"INFO {"server_id":"mock","version":"2.10.0","proto":1,"max_payload":1048576}"#;
+
+#[test]
+pub fn send_no_echo() {
+    let server = MockServer::start(PROTO1_INFO, |conn| {
+        let line = conn.read_line();
+        let connect: serde_json::Value = serde_json::from_str(&line["CONNECT ".len()..]).unwrap();
+        assert_eq!(serde_json::Value::Bool(false), connect["echo"]);
+        assert_eq!(serde_json::Value::from(1), connect["protocol"]);
+    });
+    let mut opt = ConnectOption::new();
+    opt.verbose = false;
+    opt.echo = false;
+    NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_secs(5)), Some(&opt)).unwrap();
+    server.join();
+}
+
+#[test]
+pub fn no_echo_requires_proto1() {
+    let server = MockServer::start(common::DEFAULT_INFO, |_| {});
+    let mut opt = ConnectOption::new();
+    opt.verbose = false;
+    opt.echo = false;
+    match NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_secs(5)), Some(&opt)) {
+        Err(NatsError::NoEchoNotSupported) => {}
+        _ => panic!("NoEchoNotSupported expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn deserialize_option_without_new_fields() {
+    // shape written before headers, no_responders, echo and protocol were added
+    let old = r#"{"verbose":true,"pedantic":false,"ssl_required":false,"auth_token":"","user":"u",
+        "pass":"p","name":"app","lang":"Rust","version":"0.0.1"}"#;
+    let opt: ConnectOption = serde_json::from_str(old).unwrap();
+    assert!(opt.verbose);
+    assert_eq!("u", opt.user);
+    assert!(opt.headers && opt.no_responders && opt.echo);
+    assert_eq!(1, opt.protocol);
+    let json = serde_json::to_string(&opt).unwrap();
+    let again: ConnectOption = serde_json::from_str(&json).unwrap();
+    assert_eq!(json, serde_json::to_string(&again).unwrap());
+}
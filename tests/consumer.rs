@@ -0,0 +1,348 @@
+extern crate simple_nats_client;
+extern crate serde_json;
+
+mod common;
+
+use common::{accept_client, connect, respond, MockConnection, MockServer};
+use simple_nats_client::jetstream::consumer::{AckPolicy, ConsumerConfig, DeliverPolicy, JetStreamMessage, MessageMetadata};
+use simple_nats_client::jetstream::JetStream;
+use simple_nats_client::nats_client::{NatsError, NatsMessage};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const CONSUMER_INFO: &str = r#"{"type":"io.nats.jetstream.api.v1.consumer_create_response",
+    "stream_name":"ORDERS","name":"worker","created":"2024-01-01T00:00:00Z",
+    "config":{"durable_name":"worker","deliver_policy":"all","ack_policy":"explicit",
+    "ack_wait":30000000000,"max_deliver":-1,"replay_policy":"instant","max_waiting":512,
+    "max_ack_pending":1000,"num_replicas":0},
+    "delivered":{"consumer_seq":0,"stream_seq":0},"ack_floor":{"consumer_seq":0,"stream_seq":0},
+    "num_ack_pending":0,"num_redelivered":0,"num_waiting":0,"num_pending":3}"#;
+
+/// look up consumer, read SUB of pull inbox and pull request, returns (sid, reply, request body)
+fn accept_pull(conn: &mut MockConnection, sid: u64) -> (u64, String, serde_json::Value) {
+    respond(conn, sid, "$JS.API.CONSUMER.INFO.ORDERS.worker", CONSUMER_INFO);
+    let (inbox, pull_sid) = conn.read_sub();
+    let req = conn.read_pub();
+    assert_eq!("$JS.API.CONSUMER.MSG.NEXT.ORDERS.worker", req.subject);
+    let reply = req.reply.unwrap();
+    assert!(reply.starts_with(inbox.trim_end_matches('*')));
+    (pull_sid, reply, serde_json::from_slice(&req.payload).unwrap())
+}
+
+#[test]
+pub fn manage_consumers() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let body = respond(conn, sid, "$JS.API.CONSUMER.CREATE.ORDERS.worker", CONSUMER_INFO).body();
+        assert_eq!("ORDERS", body["stream_name"]);
+        assert_eq!("worker", body["config"]["durable_name"]);
+        assert_eq!("explicit", body["config"]["ack_policy"]);
+        assert_eq!("by_start_sequence", body["config"]["deliver_policy"]);
+        assert_eq!(10, body["config"]["opt_start_seq"]);
+        // server defaults are not sent
+        assert!(body["config"].get("ack_wait").is_none());
+        assert!(body["config"].get("idle_heartbeat").is_none());
+        let body = respond(conn, sid, "$JS.API.CONSUMER.CREATE.ORDERS", CONSUMER_INFO).body();
+        assert!(body["config"].get("durable_name").is_none());
+        respond(conn, sid, "$JS.API.CONSUMER.INFO.ORDERS.worker", CONSUMER_INFO);
+        respond(conn, sid, "$JS.API.CONSUMER.DELETE.ORDERS.worker", r#"{"success":true}"#);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    let mut config = ConsumerConfig::durable("worker");
+    config.deliver_policy = DeliverPolicy::ByStartSequence;
+    config.opt_start_seq = Some(10);
+    let info = js.create_consumer(&mut c, "ORDERS", &config).unwrap();
+    assert_eq!(AckPolicy::Explicit, info.config.ack_policy);
+    assert_eq!(Duration::from_secs(30), info.config.ack_wait);
+    assert_eq!(Some(512), info.config.max_waiting);
+    js.create_consumer(&mut c, "ORDERS", &ConsumerConfig::default()).unwrap();
+    assert_eq!(3, js.consumer_info(&mut c, "ORDERS", "worker").unwrap().num_pending);
+    assert!(js.delete_consumer(&mut c, "ORDERS", "worker").unwrap());
+    server.join();
+}
+
+#[test]
+pub fn fetch_and_ack() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let (pull_sid, reply, body) = accept_pull(conn, sid);
+        assert_eq!(10, body["batch"]);
+        assert_eq!(2_000_000_000u64, body["expires"]);
+        assert_eq!(1_000_000_000u64, body["idle_heartbeat"]);
+        conn.send_msg(&reply, pull_sid, Some("$JS.ACK.ORDERS.worker.1.1.1.1700000000000000000.2"), None, b"a");
+        conn.send_msg(&reply, pull_sid, None, Some("NATS/1.0 100 Idle Heartbeat\r\n\r\n"), b"");
+        conn.send_msg(&reply,
+                      pull_sid,
+                      Some("$JS.ACK.ORDERS.worker.2.2.2.1700000000000000000.1"),
+                      Some("NATS/1.0\r\nk: v\r\n\r\n"),
+                      b"b");
+        conn.send_msg(&reply, pull_sid, None, Some("NATS/1.0 408 Request Timeout\r\n\r\n"), b"");
+
+        let ack = conn.read_pub();
+        assert_eq!("$JS.ACK.ORDERS.worker.1.1.1.1700000000000000000.2", ack.subject);
+        assert_eq!(b"+ACK".to_vec(), ack.payload);
+        let nak = conn.read_pub();
+        assert_eq!(br#"-NAK {"delay":1000000000}"#.to_vec(), nak.payload);
+        assert_eq!(b"+WPI".to_vec(), conn.read_pub().payload);
+        assert_eq!(b"+TERM".to_vec(), conn.read_pub().payload);
+
+        // next pull is published to another reply subject
+        let req = conn.read_pub();
+        let next_reply = req.reply.unwrap();
+        assert_ne!(reply, next_reply);
+        assert!(serde_json::from_slice::<serde_json::Value>(&req.payload).unwrap().get("idle_heartbeat").is_none());
+        // status of expired request is ignored
+        conn.send_msg(&reply, pull_sid, None, Some("NATS/1.0 409 Exceeded MaxWaiting\r\n\r\n"), b"");
+        conn.send_msg(&next_reply, pull_sid, None, Some("NATS/1.0 404 No Messages\r\n\r\n"), b"");
+        assert_eq!(format!("UNSUB {}", pull_sid), conn.read_line());
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    let mut consumer = js.pull_consumer(&mut c, "ORDERS", "worker").unwrap();
+    let msgs = consumer.fetch(&mut c, 10, Duration::from_secs(2)).unwrap();
+    assert_eq!(2, msgs.len());
+    assert_eq!(b"a".to_vec(), msgs[0].message.data);
+    assert_eq!(b"b".to_vec(), msgs[1].message.data);
+    let meta = msgs[1].metadata().unwrap();
+    assert_eq!(2, meta.stream_sequence);
+    assert_eq!(1, meta.pending);
+
+    msgs[0].ack(&mut c).unwrap();
+    msgs[0].nak_with_delay(&mut c, Duration::from_secs(1)).unwrap();
+    msgs[1].in_progress(&mut c).unwrap();
+    msgs[1].term(&mut c).unwrap();
+    assert!(consumer.fetch(&mut c, 1, Duration::from_millis(200)).unwrap().is_empty());
+    consumer.unsubscribe(&mut c).unwrap();
+    server.join();
+}
+
+#[test]
+pub fn fetch_errors() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        // no heartbeat is sent
+        let (pull_sid, _, _) = accept_pull(conn, sid);
+        let req = conn.read_pub();
+        conn.send_msg(&req.reply.unwrap(),
+                      pull_sid,
+                      None,
+                      Some("NATS/1.0 409 Consumer Deleted\r\n\r\n"),
+                      b"");
+        assert_eq!("done", conn.read_pub().subject);
+    });
+    let mut c = connect(server.port);
+    let mut consumer = JetStream::new().pull_consumer(&mut c, "ORDERS", "worker").unwrap();
+    let start = Instant::now();
+    match consumer.fetch(&mut c, 1, Duration::from_secs(2)) {
+        Err(NatsError::MissedHeartbeat(_)) => {}
+        other => panic!("unexpected {:?}", other.map(|v| v.len())),
+    }
+    assert!(start.elapsed() < Duration::from_millis(2900));
+    match consumer.next(&mut c, Duration::from_secs(1)) {
+        Err(NatsError::JetStream(e)) => {
+            assert_eq!(409, e.code);
+            assert_eq!("Consumer Deleted", e.description);
+        }
+        other => panic!("unexpected {:?}", other.map(|v| v.is_some())),
+    }
+    c.publish("done", None, b"").unwrap();
+    server.join();
+}
+
+#[test]
+pub fn parse_metadata() {
+    let meta = MessageMetadata::parse("$JS.ACK.ORDERS.worker.3.10.7.1700000000123456789.5").unwrap();
+    assert_eq!(None, meta.domain);
+    assert_eq!("ORDERS", meta.stream);
+    assert_eq!("worker", meta.consumer);
+    assert_eq!(3, meta.delivered);
+    assert_eq!(10, meta.stream_sequence);
+    assert_eq!(7, meta.consumer_sequence);
+    assert_eq!(5, meta.pending);
+    assert_eq!(Duration::new(1_700_000_000, 123_456_789),
+               meta.timestamp.duration_since(UNIX_EPOCH).unwrap());
+
+    let meta = MessageMetadata::parse("$JS.ACK.hub.ACCHASH.ORDERS.worker.1.2.3.1700000000000000000.0.rand").unwrap();
+    assert_eq!(Some("hub".to_owned()), meta.domain);
+    assert_eq!("ORDERS", meta.stream);
+    assert_eq!(3, meta.consumer_sequence);
+    let meta = MessageMetadata::parse("$JS.ACK._.ACCHASH.ORDERS.worker.1.2.3.1700000000000000000.0").unwrap();
+    assert_eq!(None, meta.domain);
+
+    assert!(MessageMetadata::parse("orders.new").is_err());
+    assert!(MessageMetadata::parse("$JS.ACK.ORDERS.worker.x.2.3.4.5").is_err());
+    assert!(MessageMetadata::parse("$JS.ACK.ORDERS.worker.1.2.3.4.5.6").is_err());
+}
+
+const PUSH_CONSUMER_INFO: &str = r#"{"stream_name":"ORDERS","name":"pusher","created":"2024-01-01T00:00:00Z",
+    "config":{"name":"pusher","deliver_policy":"all","ack_policy":"none","max_deliver":-1,
+    "replay_policy":"instant","deliver_subject":"deliver.pusher","idle_heartbeat":500000000,
+    "flow_control":true},
+    "delivered":{"consumer_seq":0,"stream_seq":0},"ack_floor":{"consumer_seq":0,"stream_seq":0}}"#;
+
+#[test]
+pub fn push_consumer_flow_control_and_heartbeat() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let (deliver, sid) = conn.read_sub();
+        assert_eq!("deliver.pusher", deliver);
+        let inbox_sid = conn.read_sub().1;
+        let body = respond(conn, inbox_sid, "$JS.API.CONSUMER.CREATE.ORDERS.pusher", PUSH_CONSUMER_INFO).body();
+        assert_eq!("deliver.pusher", body["config"]["deliver_subject"]);
+        assert_eq!(true, body["config"]["flow_control"]);
+
+        conn.send_msg(&deliver, sid, Some("$JS.ACK.ORDERS.pusher.1.5.1.1700000000000000000.1"), None, b"a");
+        conn.send_msg(&deliver, sid, Some("fc.reply"), Some("NATS/1.0 100 FlowControl Request\r\n\r\n"), b"");
+        conn.send_msg(&deliver, sid, Some("$JS.ACK.ORDERS.pusher.1.6.2.1700000000000000000.0"), None, b"b");
+        conn.send_msg(&deliver,
+                      sid,
+                      None,
+                      Some("NATS/1.0 100 Idle Heartbeat\r\nNats-Last-Consumer: 2\r\nNats-Consumer-Stalled: fc.stalled\r\n\r\n"),
+                      b"");
+        let fc = conn.read_pub();
+        assert_eq!("fc.reply", fc.subject);
+        assert!(fc.payload.is_empty());
+        assert_eq!("fc.stalled", conn.read_pub().subject);
+
+        // heartbeat is missed, then gaps are notified
+        assert_eq!("ordered", conn.read_pub().subject);
+        conn.send_msg(&deliver,
+                      sid,
+                      None,
+                      Some("NATS/1.0 100 Idle Heartbeat\r\nNats-Last-Consumer: 3\r\nNats-Last-Stream: 7\r\n\r\n"),
+                      b"");
+        conn.send_msg(&deliver, sid, Some("$JS.ACK.ORDERS.pusher.1.9.4.1700000000000000000.0"), None, b"c");
+        assert_eq!("done", conn.read_pub().subject);
+    });
+    let mut c = connect(server.port);
+    let config = ConsumerConfig {
+        name: Some("pusher".to_owned()),
+        ack_policy: AckPolicy::None,
+        deliver_subject: Some("deliver.pusher".to_owned()),
+        flow_control: true,
+        idle_heartbeat: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let mut consumer = JetStream::new().push_subscribe(&mut c, "ORDERS", &config).unwrap();
+    let timeout = Duration::from_secs(5);
+    assert_eq!(b"a".to_vec(), consumer.next(&mut c, timeout).unwrap().unwrap().message.data);
+    assert_eq!(b"b".to_vec(), consumer.next(&mut c, timeout).unwrap().unwrap().message.data);
+    assert_eq!(6, consumer.last_stream_sequence());
+    assert_eq!(2, consumer.last_consumer_sequence());
+
+    let start = Instant::now();
+    match consumer.next(&mut c, timeout) {
+        Err(NatsError::MissedHeartbeat(_)) => {}
+        other => panic!("unexpected {:?}", other.map(|v| v.is_some())),
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    consumer.set_ordered(true);
+    c.publish("ordered", None, b"").unwrap();
+    for received in &[3, 4] {
+        match consumer.next(&mut c, timeout) {
+            Err(NatsError::ConsumerSequenceMismatch { stream_sequence, consumer_sequence, received: r }) => {
+                assert_eq!(6, stream_sequence);
+                assert_eq!(2, consumer_sequence);
+                assert_eq!(*received, r);
+            }
+            other => panic!("unexpected {:?}", other.map(|v| v.is_some())),
+        }
+    }
+    c.publish("done", None, b"").unwrap();
+    server.join();
+}
+
+fn ephemeral_info(name: &str) -> String {
+    format!(r#"{{"stream_name":"ORDERS","name":"{}","created":"2024-01-01T00:00:00Z",
+        "config":{{"ack_policy":"none","deliver_policy":"all","idle_heartbeat":500000000,"flow_control":true}}}}"#,
+            name)
+}
+
+fn send_ordered(conn: &mut MockConnection, deliver: &str, sid: u64, name: &str, sseq: u64, cseq: u64) {
+    let ack = format!("$JS.ACK.ORDERS.{}.1.{}.{}.1700000000000000000.0", name, sseq, cseq);
+    conn.send_msg(deliver, sid, Some(&ack), None, sseq.to_string().as_bytes());
+}
+
+#[test]
+pub fn ordered_consumer_recreated_on_gap() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let (deliver, sid) = conn.read_sub();
+        let inbox_sid = conn.read_sub().1;
+        let body = respond(conn, inbox_sid, "$JS.API.CONSUMER.CREATE.ORDERS", &ephemeral_info("e1")).body();
+        let config = &body["config"];
+        assert_eq!(deliver, config["deliver_subject"]);
+        assert_eq!("none", config["ack_policy"]);
+        assert_eq!("all", config["deliver_policy"]);
+        assert_eq!("orders.>", config["filter_subject"]);
+        assert_eq!(true, config["flow_control"]);
+        assert_eq!(true, config["mem_storage"]);
+        assert_eq!(500_000_000, config["idle_heartbeat"]);
+        assert_eq!(300_000_000_000u64, config["inactive_threshold"]);
+
+        send_ordered(conn, &deliver, sid, "e1", 1, 1);
+        send_ordered(conn, &deliver, sid, "e1", 2, 2);
+        // consumer sequence 3 is lost
+        send_ordered(conn, &deliver, sid, "e1", 5, 4);
+
+        // old subscription is drained and consumer is deleted
+        assert_eq!(format!("UNSUB {}", sid), conn.read_line());
+        assert_eq!("PING", conn.read_line());
+        conn.send("PONG\r\n");
+        respond(conn, inbox_sid, "$JS.API.CONSUMER.DELETE.ORDERS.e1", r#"{"success":true}"#);
+
+        let (deliver, sid) = conn.read_sub();
+        let body = respond(conn, inbox_sid, "$JS.API.CONSUMER.CREATE.ORDERS", &ephemeral_info("e2")).body();
+        assert_eq!("by_start_sequence", body["config"]["deliver_policy"]);
+        assert_eq!(3, body["config"]["opt_start_seq"]);
+        send_ordered(conn, &deliver, sid, "e2", 3, 1);
+        send_ordered(conn, &deliver, sid, "e2", 4, 2);
+
+        assert_eq!(format!("UNSUB {}", sid), conn.read_line());
+        respond(conn, inbox_sid, "$JS.API.CONSUMER.DELETE.ORDERS.e2", r#"{"success":true}"#);
+    });
+    let mut c = connect(server.port);
+    let config = ConsumerConfig {
+        filter_subject: Some("orders.>".to_owned()),
+        idle_heartbeat: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let mut consumer = JetStream::new().ordered_consumer(&mut c, "ORDERS", &config).unwrap();
+    let data: Vec<Vec<u8>> = consumer.messages(&mut c, Duration::from_millis(300))
+        .map(|msg| msg.unwrap().message.data)
+        .collect();
+    assert_eq!(vec![b"1".to_vec(), b"2".to_vec(), b"3".to_vec(), b"4".to_vec()], data);
+    assert_eq!(1, consumer.resets());
+    assert_eq!(4, consumer.last_stream_sequence());
+    consumer.close(&mut c).unwrap();
+    server.join();
+}
+
+#[test]
+pub fn consumer_kind_mismatch_and_not_ackable() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        respond(conn, sid, "$JS.API.CONSUMER.INFO.ORDERS.pusher", PUSH_CONSUMER_INFO);
+        respond(conn, sid, "$JS.API.CONSUMER.INFO.ORDERS.worker", CONSUMER_INFO);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    match js.pull_consumer(&mut c, "ORDERS", "pusher") {
+        Err(NatsError::ConsumerKindMismatch { ref name, push: true }) => assert_eq!("pusher", name),
+        _ => panic!("ConsumerKindMismatch expected"),
+    }
+    match js.push_consumer(&mut c, "ORDERS", "worker") {
+        Err(e @ NatsError::ConsumerKindMismatch { push: false, .. }) => {
+            assert_eq!("consumer worker is pull consumer", e.to_string());
+            assert!(!e.is_retryable());
+        }
+        _ => panic!("ConsumerKindMismatch expected"),
+    }
+    let msg = JetStreamMessage::new(NatsMessage::new(0, "orders.new", None, b"order".to_vec()));
+    match msg.ack(&mut c) {
+        Err(NatsError::NotAckable(ref subject)) => assert_eq!("orders.new", subject),
+        _ => panic!("NotAckable expected"),
+    }
+    server.join();
+}
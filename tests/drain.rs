@@ -0,0 +1,85 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::{connect, MockServer};
+use simple_nats_client::nats_client::{NatsError, NatsResponse};
+use std::time::Duration;
+
+#[test]
+pub fn drain_subscription_and_connection() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        assert_eq!("SUB bar 1", conn.read_line());
+        assert_eq!("UNSUB 0", conn.read_line());
+        assert_eq!("PING", conn.read_line());
+        conn.send("MSG foo 0 1\r\n1\r\nMSG bar 1 1\r\n2\r\nMSG foo 0 1\r\n3\r\nPONG\r\n");
+        assert_eq!("PUB out 3", conn.read_line());
+        assert_eq!(b"abc".to_vec(), conn.read_payload(3));
+        assert_eq!("UNSUB 1", conn.read_line());
+        assert_eq!("PING", conn.read_line());
+        conn.send("MSG bar 1 1\r\n4\r\nPONG\r\n");
+        // connection is closed after draining
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = connect(server.port);
+    let foo = c.subscribe("foo", None).unwrap();
+    c.subscribe("bar", None).unwrap();
+    let mut drained = Vec::new();
+    c.drain_subscription(foo, Duration::from_secs(5), |msg| drained.push(msg.data))
+        .unwrap();
+    assert_eq!(vec![b"1".to_vec(), b"3".to_vec()], drained);
+    // message of other subscription is kept
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"2", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    c.publish("out", None, b"abc").unwrap();
+    let mut drained = Vec::new();
+    c.drain(Duration::from_secs(5), |msg| drained.push(msg.data)).unwrap();
+    assert_eq!(vec![b"4".to_vec()], drained);
+    server.join();
+}
+
+#[test]
+pub fn drain_timeout() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("PING", conn.read_line());
+        // PONG is never sent
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = connect(server.port);
+    match c.drain(Duration::from_millis(100), |_| panic!("no message expected")) {
+        Err(NatsError::Timeout { .. }) => {}
+        _ => panic!("timeout expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn drain_discards_late_reply() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        let (_, sid) = conn.read_sub();
+        let req = conn.read_pub();
+        assert_eq!("UNSUB 0", conn.read_line());
+        assert_eq!(format!("UNSUB {}", sid), conn.read_line());
+        assert_eq!("PING", conn.read_line());
+        conn.send_msg(&req.reply.unwrap(), sid, None, None, b"late");
+        conn.send("MSG foo 0 1\r\n1\r\nPONG\r\n");
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = connect(server.port);
+    c.subscribe("foo", None).unwrap();
+    match c.request("service", b"", Duration::from_millis(100)) {
+        Err(NatsError::Timeout { .. }) => {}
+        _ => panic!("Timeout expected"),
+    }
+    let mut drained = Vec::new();
+    c.drain(Duration::from_secs(5), |msg| drained.push(msg.data)).unwrap();
+    assert_eq!(vec![b"1".to_vec()], drained);
+    server.join();
+}
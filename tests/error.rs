@@ -0,0 +1,53 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{NatsClient, NatsError, NatsServerError};
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+#[test]
+pub fn error_source_chain() {
+    let e = NatsError::ServerError(NatsServerError::new("'Stale Connection'"));
+    assert_eq!("'Stale Connection'", e.source().unwrap().to_string());
+    assert!(e.is_retryable());
+    let e = NatsError::ConnectionError {
+        error: io::Error::new(io::ErrorKind::ConnectionReset, "reset"),
+        operation: "test".to_owned(),
+        server: "127.0.0.1:4222".to_owned(),
+    };
+    assert_eq!("reset", e.source().unwrap().to_string());
+    assert_eq!("connection error(test, server 127.0.0.1:4222): reset", e.to_string());
+    assert!(e.is_retryable());
+    let boxed: Box<dyn Error> = Box::new(NatsError::ServerError(NatsServerError::new("'Permissions Violation for Publish to \"foo\"'")));
+    assert!(boxed.source().is_some());
+    let e = NatsError::InvalidSubject {
+        subject: "a b".to_owned(),
+        reason: "test",
+    };
+    assert!(e.source().is_none());
+    assert!(!e.is_retryable());
+}
+
+#[test]
+pub fn read_timeout() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        // keep connection open until client gives up
+        conn.read_line();
+    });
+    let mut c = NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_millis(100)), None)
+        .unwrap();
+    match c.wait_message() {
+        Err(e @ NatsError::Timeout { .. }) => {
+            assert!(e.is_retryable());
+            // io error of socket read is kept
+            assert!(e.source().is_some());
+        }
+        Err(e) => panic!("timeout expected:{}", e),
+        Ok(_) => panic!("timeout expected"),
+    }
+    drop(c);
+    server.join();
+}
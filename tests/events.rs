@@ -0,0 +1,53 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::connect_option::ConnectOption;
+use simple_nats_client::nats_client::{NatsClient, NatsResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+pub fn lifecycle_events() {
+    let next = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        conn.send("MSG foo 0 4\r\nnext\r\n");
+        assert_eq!("", conn.read_line());
+    });
+    let next_server = format!("127.0.0.1:{}", next.port);
+    let info = format!("INFO {{\"server_id\":\"old\",\"max_payload\":1048576,\"connect_urls\":[\"{}\"]}}",
+                       next_server);
+    let old = MockServer::start(&info, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+    });
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut opt = ConnectOption::new();
+    opt.verbose = false;
+    let e = events.clone();
+    opt.set_discovered_servers_handler(move |servers| {
+        e.lock().unwrap().push(format!("discovered {}", servers.join(",")))
+    });
+    let e = events.clone();
+    opt.set_disconnected_handler(move |server, _| e.lock().unwrap().push(format!("disconnected {}", server)));
+    let e = events.clone();
+    opt.set_reconnected_handler(move |server| e.lock().unwrap().push(format!("reconnected {}", server)));
+    let e = events.clone();
+    opt.set_closed_handler(move || e.lock().unwrap().push("closed".to_owned()));
+    let mut c = NatsClient::new_with_option("127.0.0.1", old.port, Some(Duration::from_secs(5)), Some(&opt))
+        .unwrap();
+    c.set_reconnect(true);
+    let old_port = old.port;
+    old.join();
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"next", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    c.close().unwrap();
+    next.join();
+    assert_eq!(vec![format!("discovered {}", next_server),
+                    format!("disconnected 127.0.0.1:{}", old_port),
+                    format!("reconnected {}", next_server),
+                    "closed".to_owned()],
+               *events.lock().unwrap());
+}
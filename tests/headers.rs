@@ -0,0 +1,59 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{ConnectOption, Headers, NatsClient, NatsResponse};
+use std::time::Duration;
+
+fn connect(port: i32) -> NatsClient {
+    let opt = ConnectOption::new_with_param("", "", false, "headers_test");
+    NatsClient::new_with_option("127.0.0.1", port, Some(Duration::from_secs(5)), Some(&opt)).unwrap()
+}
+
+#[test]
+pub fn receive_message_with_headers() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        conn.send("HMSG foo 0 reply 28 31\r\nNATS/1.0\r\nkey: a\r\nkey: b\r\n\r\nabc\r\n");
+        conn.send("HMSG foo 0 30 30\r\nNATS/1.0 503 No Responders\r\n\r\n\r\n");
+    });
+    let mut c = connect(server.port);
+    let sid = c.subscribe("foo", None).unwrap();
+    match c.wait_message().unwrap() {
+        NatsResponse::Msg(v) => {
+            assert_eq!(sid, v.sid);
+            assert_eq!(Some("reply".to_owned()), v.reply);
+            assert_eq!(b"abc".to_vec(), v.data);
+            let headers = v.headers.unwrap();
+            assert_eq!(vec!["a", "b"], headers.get_all("key").collect::<Vec<_>>());
+            assert_eq!(None, headers.status());
+        }
+        _ => panic!("HMSG expected"),
+    }
+    match c.wait_message().unwrap() {
+        NatsResponse::Msg(v) => {
+            assert!(v.data.is_empty());
+            let headers = v.headers.unwrap();
+            assert_eq!(Some(503), headers.status());
+            assert_eq!(Some("No Responders"), headers.description());
+        }
+        _ => panic!("HMSG expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn publish_with_headers() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("HPUB foo reply 20 23", conn.read_line());
+        assert_eq!(b"NATS/1.0\r\nkey: a\r\n\r\nabc".to_vec(), conn.read_payload(23));
+    });
+    let mut c = connect(server.port);
+    let mut headers = Headers::new();
+    headers.insert("key", "a");
+    c.publish_with_headers("foo", Some("reply"), &headers, b"abc").unwrap();
+    server.join();
+}
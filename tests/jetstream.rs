@@ -0,0 +1,336 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::{accept_client, connect, respond, MockServer};
+use simple_nats_client::jetstream::{ApiError, JetStream, PublishOptions, StorageType, StreamConfig};
+use simple_nats_client::nats_client::{NatsError, NatsResponse};
+use std::time::{Duration, UNIX_EPOCH};
+
+const STREAM_INFO: &str = r#"{"type":"io.nats.jetstream.api.v1.stream_create_response",
+    "config":{"name":"ORDERS","subjects":["orders.>"],"retention":"limits","max_consumers":-1,
+    "max_msgs":-1,"max_bytes":-1,"max_age":3600000000000,"max_msgs_per_subject":-1,"max_msg_size":-1,
+    "discard":"old","storage":"memory","num_replicas":1,"duplicate_window":120000000000,
+    "compression":"none"},
+    "created":"2024-01-01T00:00:00Z",
+    "state":{"messages":3,"bytes":120,"first_seq":1,"first_ts":"2024-01-01T00:00:00Z","last_seq":3,
+    "last_ts":"2024-01-01T00:00:01Z","consumer_count":0}}"#;
+
+#[test]
+pub fn manage_streams() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let body = respond(conn, sid, "$JS.API.STREAM.CREATE.ORDERS", STREAM_INFO).body();
+        assert_eq!("ORDERS", body["name"]);
+        assert_eq!("memory", body["storage"]);
+        assert_eq!(3_600_000_000_000u64, body["max_age"]);
+        let body = respond(conn, sid, "$JS.API.STREAM.UPDATE.ORDERS", STREAM_INFO).body();
+        // unknown fields are sent back
+        assert_eq!("none", body["compression"]);
+        respond(conn, sid, "$JS.API.STREAM.PURGE.ORDERS", r#"{"success":true,"purged":3}"#);
+        let body = respond(conn, sid, "$JS.API.STREAM.PURGE.ORDERS", r#"{"success":true,"purged":1}"#).body();
+        assert_eq!("orders.a", body["filter"]);
+        assert!(body.get("seq").is_none());
+        respond(conn, sid, "$JS.API.STREAM.DELETE.ORDERS", r#"{"success":true}"#);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    let mut config = StreamConfig::new("ORDERS");
+    config.subjects = vec!["orders.>".to_owned()];
+    config.storage = StorageType::Memory;
+    config.max_age = Duration::from_secs(3600);
+    let info = js.create_stream(&mut c, &config).unwrap();
+    assert_eq!(3, info.state.messages);
+    assert_eq!(Duration::from_secs(120), info.config.duplicate_window);
+    js.update_stream(&mut c, &info.config).unwrap();
+    assert_eq!(3, js.purge_stream(&mut c, "ORDERS").unwrap().purged);
+    let request = simple_nats_client::jetstream::PurgeRequest {
+        filter: Some("orders.a".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(1, js.purge_stream_with(&mut c, "ORDERS", &request).unwrap().purged);
+    assert!(js.delete_stream(&mut c, "ORDERS").unwrap());
+    server.join();
+}
+
+#[test]
+pub fn api_error() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        respond(conn,
+                sid,
+                "$JS.API.STREAM.INFO.MISSING",
+                r#"{"error":{"code":404,"err_code":10059,"description":"stream not found"}}"#);
+    });
+    let mut c = connect(server.port);
+    match JetStream::new().stream_info(&mut c, "MISSING") {
+        Err(NatsError::JetStream(e)) => {
+            assert_eq!(404, e.code);
+            assert_eq!(ApiError::STREAM_NOT_FOUND, e.err_code);
+            assert_eq!("stream not found", e.description);
+        }
+        _ => panic!("JetStream error expected"),
+    }
+    // invalid name is rejected without request
+    match JetStream::new().stream_info(&mut c, "a.b") {
+        Err(NatsError::InvalidSubject { .. }) => {}
+        _ => panic!("InvalidSubject expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn list_streams_with_paging() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let body = respond(conn,
+                           sid,
+                           "$JS.API.STREAM.NAMES",
+                           r#"{"total":3,"offset":0,"limit":2,"streams":["A","B"]}"#).body();
+        assert_eq!(0, body["offset"]);
+        let body = respond(conn,
+                           sid,
+                           "$JS.API.STREAM.NAMES",
+                           r#"{"total":3,"offset":2,"limit":2,"streams":["C"]}"#).body();
+        assert_eq!(2, body["offset"]);
+        respond(conn, sid, "$JS.API.STREAM.LIST", r#"{"total":0,"offset":0,"limit":256,"streams":null}"#);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    assert_eq!(vec!["A", "B", "C"], js.stream_names(&mut c).unwrap());
+    assert!(js.list_streams(&mut c).unwrap().is_empty());
+    server.join();
+}
+
+#[test]
+pub fn publish_with_ack() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let req = conn.read_pub();
+        assert_eq!("orders.new", req.subject);
+        assert!(req.headers.is_none());
+        conn.send_msg(&req.reply.unwrap(), sid, None, None, br#"{"stream":"ORDERS","seq":1}"#);
+        let req = conn.read_pub();
+        let headers = req.headers.unwrap();
+        assert!(headers.contains("Nats-Msg-Id: id-1\r\n"));
+        assert!(headers.contains("Nats-Expected-Stream: ORDERS\r\n"));
+        assert!(headers.contains("Nats-Expected-Last-Sequence: 1\r\n"));
+        conn.send_msg(&req.reply.unwrap(), sid, None, None, br#"{"stream":"ORDERS","seq":1,"duplicate":true}"#);
+        let req = conn.read_pub();
+        conn.send_msg(&req.reply.unwrap(),
+                      sid,
+                      None,
+                      None,
+                      br#"{"error":{"code":400,"err_code":10071,"description":"wrong last sequence: 1"}}"#);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    let ack = js.publish(&mut c, "orders.new", b"1").unwrap();
+    assert_eq!(("ORDERS", 1, false), (&ack.stream[..], ack.seq, ack.duplicate));
+    let opts = PublishOptions {
+        msg_id: Some("id-1".to_owned()),
+        expected_stream: Some("ORDERS".to_owned()),
+        expected_last_sequence: Some(1),
+        ..Default::default()
+    };
+    assert!(js.publish_with_options(&mut c, "orders.new", b"1", &opts).unwrap().duplicate);
+    let opts = PublishOptions {
+        expected_last_sequence: Some(0),
+        ..Default::default()
+    };
+    match js.publish_with_options(&mut c, "orders.new", b"2", &opts) {
+        Err(NatsError::JetStream(e)) => assert_eq!(ApiError::STREAM_WRONG_LAST_SEQUENCE, e.err_code),
+        _ => panic!("JetStream error expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn async_publish() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let first = conn.read_pub().reply.unwrap();
+        let second = conn.read_pub().reply.unwrap();
+        // third publish waits for an acknowledgement
+        conn.send_msg(&second, sid, None, None, br#"{"stream":"S","seq":2}"#);
+        let third = conn.read_pub().reply.unwrap();
+        conn.send_msg(&first, sid, None, None, br#"{"stream":"S","seq":1}"#);
+        conn.send_msg(&third, sid, None, Some("NATS/1.0 503\r\n\r\n"), b"");
+    });
+    let mut c = connect(server.port);
+    let mut publisher = JetStream::new().async_publisher(2);
+    for i in 0..3 {
+        assert_eq!(i, publisher.publish(&mut c, "s", b"x", &PublishOptions::default()).unwrap());
+    }
+    assert_eq!(2, publisher.in_flight());
+    let completed = publisher.flush(&mut c).unwrap();
+    let ids: Vec<u64> = completed.iter().map(|v| v.0).collect();
+    assert_eq!(vec![1, 0, 2], ids);
+    assert_eq!(2, completed[0].1.as_ref().unwrap().seq);
+    assert_eq!(1, completed[1].1.as_ref().unwrap().seq);
+    match completed[2].1 {
+        Err(NatsError::NoResponders(_)) => {}
+        _ => panic!("NoResponders expected"),
+    }
+    assert_eq!(0, publisher.in_flight());
+    server.join();
+}
+
+#[test]
+pub fn async_publish_with_wait_message() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let first = conn.read_pub().reply.unwrap();
+        let second = conn.read_pub().reply.unwrap();
+        let (_, events) = conn.read_sub();
+        // acknowledgements arrive before and after message of another subscription
+        conn.send_msg(&first, sid, None, None, br#"{"stream":"S","seq":1}"#);
+        conn.send_msg("events", events, None, None, b"e");
+        conn.send_msg(&second, sid, None, None, br#"{"stream":"S","seq":2}"#);
+    });
+    let mut c = connect(server.port);
+    let mut publisher = JetStream::new().async_publisher(2);
+    publisher.publish(&mut c, "s", b"x", &PublishOptions::default()).unwrap();
+    publisher.publish(&mut c, "s", b"y", &PublishOptions::default()).unwrap();
+    c.subscribe("events", None).unwrap();
+    match c.wait_message().unwrap() {
+        NatsResponse::Msg(msg) => assert_eq!("events", msg.subject),
+        _ => panic!("MSG expected"),
+    }
+    let completed = publisher.flush(&mut c).unwrap();
+    let seqs: Vec<u64> = completed.iter().map(|v| v.1.as_ref().unwrap().seq).collect();
+    assert_eq!(vec![1, 2], seqs);
+    server.join();
+}
+
+#[test]
+pub fn account_info_with_domain() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        respond(conn,
+                sid,
+                "$JS.hub.API.INFO",
+                r#"{"type":"io.nats.jetstream.api.v1.account_info_response","memory":0,"storage":1024,
+                "reserved_memory":0,"reserved_storage":0,"streams":2,"consumers":3,
+                "limits":{"max_memory":-1,"max_storage":1048576,"max_streams":10,"max_consumers":-1,
+                "max_ack_pending":-1,"memory_max_stream_bytes":-1,"storage_max_stream_bytes":-1,
+                "max_bytes_required":true},
+                "domain":"hub","api":{"total":5,"errors":1},
+                "tiers":{"R1":{"memory":0,"storage":1024,"streams":2,"consumers":3,
+                "limits":{"max_storage":1048576,"max_streams":10}}}}"#);
+        respond(conn, sid, "$JS.other.API.STREAM.DELETE.ORDERS", r#"{"success":true}"#);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::with_domain("hub");
+    assert_eq!("$JS.hub.API", js.api_prefix());
+    assert_eq!("$JS.API", JetStream::with_domain("").api_prefix());
+    let info = js.account_info(&mut c).unwrap();
+    assert_eq!(1024, info.storage);
+    assert_eq!(2, info.streams);
+    assert_eq!(3, info.consumers);
+    assert_eq!(1_048_576, info.limits.max_storage);
+    assert_eq!(-1, info.limits.max_memory);
+    assert!(info.limits.max_bytes_required);
+    assert_eq!(Some("hub".to_owned()), info.domain);
+    assert_eq!(5, info.api.total);
+    assert_eq!(1, info.api.errors);
+    let tier = &info.tiers["R1"];
+    assert_eq!(1024, tier.storage);
+    assert_eq!(10, tier.limits.max_streams);
+    assert_eq!(-1, tier.limits.max_consumers);
+
+    let js = JetStream::with_api_prefix("$JS.other.API.");
+    assert!(js.delete_stream(&mut c, "ORDERS").unwrap());
+    server.join();
+}
+
+#[test]
+pub fn direct_get() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let req = conn.read_pub();
+        assert_eq!("$JS.API.DIRECT.GET.ORDERS", req.subject);
+        assert_eq!(r#"{"seq":2}"#.as_bytes(), &req.payload[..]);
+        conn.send_msg(&req.reply.unwrap(),
+                      sid,
+                      None,
+                      Some("NATS/1.0\r\nNats-Stream: ORDERS\r\nNats-Subject: orders.new\r\nNats-Sequence: 2\r\n\
+                            Nats-Time-Stamp: 2024-01-01T00:00:01.5Z\r\nX-Trace: abc\r\n\r\n"),
+                      b"order");
+
+        let req = conn.read_pub();
+        assert_eq!(r#"{"last_by_subj":"orders.old"}"#.as_bytes(), &req.payload[..]);
+        conn.send_msg(&req.reply.unwrap(), sid, None, Some("NATS/1.0 404 Message Not Found\r\n\r\n"), b"");
+
+        // stream without allow_direct
+        let req = conn.read_pub();
+        assert_eq!("$JS.API.DIRECT.GET.ORDERS", req.subject);
+        conn.send_msg(&req.reply.unwrap(), sid, None, Some("NATS/1.0 503\r\n\r\n"), b"");
+        let body = respond(conn,
+                           sid,
+                           "$JS.API.STREAM.MSG.GET.ORDERS",
+                           r#"{"message":{"subject":"orders.new","seq":3,"data":"b3JkZXI=",
+                           "time":"2024-01-01T00:00:02Z"}}"#).body();
+        assert_eq!("orders.new", body["last_by_subj"]);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    let msg = js.direct_get_message(&mut c, "ORDERS", 2).unwrap();
+    assert_eq!("orders.new", msg.subject);
+    assert_eq!(2, msg.sequence);
+    assert_eq!(b"order".to_vec(), msg.data);
+    assert_eq!(Duration::new(1_704_067_201, 500_000_000),
+               msg.time.duration_since(UNIX_EPOCH).unwrap());
+    let headers = msg.headers.unwrap();
+    assert_eq!(1, headers.len());
+    assert_eq!(Some("abc"), headers.get("X-Trace"));
+
+    match js.direct_get_last_message(&mut c, "ORDERS", "orders.old") {
+        Err(NatsError::JetStream(e)) => assert_eq!(ApiError::NO_MESSAGE_FOUND, e.err_code),
+        other => panic!("unexpected {:?}", other),
+    }
+    let msg = js.direct_get_last_message(&mut c, "ORDERS", "orders.new").unwrap();
+    assert_eq!(3, msg.sequence);
+    assert_eq!(b"order".to_vec(), msg.data);
+    assert!(msg.headers.is_none());
+    server.join();
+}
+
+const TIMES: &[(&str, Option<(u64, u32)>)] = &[("2024-01-01T00:00:02Z", Some((1_704_067_202, 0))),
+                                               ("2024-01-01T00:00:02.123456789Z", Some((1_704_067_202, 123_456_789))),
+                                               // digits after nanoseconds are truncated
+                                               ("2024-01-01T00:00:02.1234567891Z", Some((1_704_067_202, 123_456_789))),
+                                               ("2024-01-01T09:00:02.5+09:00", Some((1_704_067_202, 500_000_000))),
+                                               ("2023-12-31T19:30:02.05-04:30", Some((1_704_067_202, 50_000_000))),
+                                               ("2024-02-29t00:00:00z", Some((1_709_164_800, 0))),
+                                               ("2024-01-01T00:00:02.Z", None),
+                                               ("2024-01-01T00:00:02+0900", None),
+                                               // offset is not ASCII
+                                               ("2024-01-01T00:00:02é0:00", None),
+                                               ("2024-01-01 00:00:02Z", None),
+                                               ("2024-13-01T00:00:02Z", None)];
+
+#[test]
+pub fn parse_rfc3339_time() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        for &(time, _) in TIMES {
+            respond(conn,
+                    sid,
+                    "$JS.API.STREAM.MSG.GET.ORDERS",
+                    &format!(r#"{{"message":{{"subject":"orders.new","seq":1,"time":"{}"}}}}"#, time));
+        }
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    for &(time, expected) in TIMES {
+        match (js.get_message(&mut c, "ORDERS", 1), expected) {
+            (Ok(msg), Some((secs, nanos))) => {
+                assert_eq!(Duration::new(secs, nanos), msg.time.duration_since(UNIX_EPOCH).unwrap(), "{}", time)
+            }
+            (Err(NatsError::MessageParseError(..)), None) => {}
+            (other, _) => panic!("unexpected {:?} for {}", other, time),
+        }
+    }
+    server.join();
+}
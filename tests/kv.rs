@@ -0,0 +1,212 @@
+extern crate base64;
+extern crate simple_nats_client;
+
+mod common;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use common::{accept_client, accept_consumer, close_consumer, connect, consumer_info, respond, MockServer};
+use simple_nats_client::jetstream::kv::{KvConfig, Operation};
+use simple_nats_client::jetstream::{ApiError, JetStream};
+use simple_nats_client::nats_client::NatsError;
+use std::time::{Duration, UNIX_EPOCH};
+
+const STREAM_INFO: &str = r#"{"config":{"name":"KV_config","subjects":["$KV.config.>"],"retention":"limits",
+    "max_consumers":-1,"max_msgs":-1,"max_bytes":-1,"max_age":0,"max_msgs_per_subject":5,"max_msg_size":-1,
+    "discard":"new","storage":"file","num_replicas":1,"duplicate_window":120000000000},
+    "created":"2024-01-01T00:00:00Z","state":{"messages":0,"bytes":0,"first_seq":0,"last_seq":0}}"#;
+
+fn stored(subject: &str, seq: u64, headers: Option<&str>, data: &[u8]) -> String {
+    let hdrs = headers.map(|h| format!(r#","hdrs":"{}""#, BASE64.encode(h))).unwrap_or_default();
+    format!(r#"{{"message":{{"subject":"{}","seq":{},"data":"{}","time":"2024-01-02T03:04:05.5Z"{}}}}}"#,
+            subject,
+            seq,
+            BASE64.encode(data),
+            hdrs)
+}
+
+fn api_error(code: u16, err_code: u64, description: &str) -> String {
+    format!(r#"{{"error":{{"code":{},"err_code":{},"description":"{}"}}}}"#, code, err_code, description)
+}
+
+#[test]
+pub fn put_get_update_delete() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        let sid = accept_client(conn);
+        let req = respond(conn, sid, "$JS.API.STREAM.CREATE.KV_config", STREAM_INFO);
+        let config = req.body();
+        assert_eq!("KV_config", config["name"]);
+        assert_eq!("$KV.config.>", config["subjects"][0]);
+        assert_eq!(5, config["max_msgs_per_subject"]);
+        assert_eq!("new", config["discard"]);
+        assert_eq!(true, config["allow_rollup_hdrs"]);
+        assert_eq!(true, config["deny_delete"]);
+        assert_eq!(true, config["allow_direct"]);
+        assert_eq!(120_000_000_000u64, config["duplicate_window"]);
+
+        let req = respond(conn, sid, "$KV.config.app.timeout", r#"{"stream":"KV_config","seq":1}"#);
+        assert_eq!(b"10".to_vec(), req.payload);
+        let req = respond(conn, sid, "$KV.config.app.timeout", r#"{"stream":"KV_config","seq":2}"#);
+        assert!(req.headers.unwrap().contains("Nats-Expected-Last-Subject-Sequence: 1\r\n"));
+
+        let req = respond(conn,
+                          sid,
+                          "$JS.API.STREAM.MSG.GET.KV_config",
+                          &stored("$KV.config.app.timeout", 2, None, b"20"));
+        assert_eq!("$KV.config.app.timeout", req.body()["last_by_subj"]);
+        respond(conn, sid, "$JS.API.STREAM.MSG.GET.KV_config", &api_error(404, 10037, "no message found"));
+
+        // create fails because key exists
+        let wrong_sequence = api_error(400, 10071, "wrong last sequence: 2");
+        let req = respond(conn, sid, "$KV.config.app.timeout", &wrong_sequence);
+        assert!(req.headers.unwrap().contains("Nats-Expected-Last-Subject-Sequence: 0\r\n"));
+        respond(conn,
+                sid,
+                "$JS.API.STREAM.MSG.GET.KV_config",
+                &stored("$KV.config.app.timeout", 2, None, b"20"));
+
+        let req = respond(conn, sid, "$KV.config.app.timeout", r#"{"stream":"KV_config","seq":3}"#);
+        assert!(req.headers.unwrap().contains("KV-Operation: DEL\r\n"));
+        assert!(req.payload.is_empty());
+
+        // create succeeds after delete
+        respond(conn, sid, "$KV.config.app.timeout", &wrong_sequence);
+        respond(conn,
+                sid,
+                "$JS.API.STREAM.MSG.GET.KV_config",
+                &stored("$KV.config.app.timeout", 3, Some("NATS/1.0\r\nKV-Operation: DEL\r\n\r\n"), b""));
+        let req = respond(conn, sid, "$KV.config.app.timeout", r#"{"stream":"KV_config","seq":4}"#);
+        assert!(req.headers.unwrap().contains("Nats-Expected-Last-Subject-Sequence: 3\r\n"));
+
+        let req = respond(conn, sid, "$KV.config.app.timeout", r#"{"stream":"KV_config","seq":5}"#);
+        let headers = req.headers.unwrap();
+        assert!(headers.contains("KV-Operation: PURGE\r\n"));
+        assert!(headers.contains("Nats-Rollup: sub\r\n"));
+        respond(conn,
+                sid,
+                "$JS.API.STREAM.MSG.GET.KV_config",
+                &stored("$KV.config.app.timeout", 5, Some("NATS/1.0\r\nKV-Operation: PURGE\r\n\r\n"), b""));
+
+        respond(conn, sid, "$JS.API.STREAM.DELETE.KV_config", r#"{"success":true}"#);
+    });
+    let mut c = connect(server.port);
+    let js = JetStream::new();
+    let mut config = KvConfig::new("config");
+    config.history = 5;
+    let kv = js.create_key_value(&mut c, &config).unwrap();
+    assert_eq!("KV_config", kv.stream_name());
+
+    assert_eq!(1, kv.put(&mut c, "app.timeout", b"10").unwrap());
+    assert_eq!(2, kv.update(&mut c, "app.timeout", b"20", 1).unwrap());
+    let entry = kv.get(&mut c, "app.timeout").unwrap().unwrap();
+    assert_eq!("config", entry.bucket);
+    assert_eq!("app.timeout", entry.key);
+    assert_eq!(b"20".to_vec(), entry.value);
+    assert_eq!(2, entry.revision);
+    assert_eq!(Operation::Put, entry.operation);
+    assert_eq!(Duration::new(1_704_164_645, 500_000_000),
+               entry.created.duration_since(UNIX_EPOCH).unwrap());
+    assert!(kv.get(&mut c, "missing").unwrap().is_none());
+
+    match kv.create(&mut c, "app.timeout", b"30") {
+        Err(NatsError::JetStream(e)) => assert_eq!(ApiError::STREAM_WRONG_LAST_SEQUENCE, e.err_code),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(3, kv.delete(&mut c, "app.timeout").unwrap());
+    assert_eq!(4, kv.create(&mut c, "app.timeout", b"30").unwrap());
+    assert_eq!(5, kv.purge(&mut c, "app.timeout").unwrap());
+    assert!(kv.get(&mut c, "app.timeout").unwrap().is_none());
+
+    for key in &["", ".app", "app.", "app..timeout", "app.*", "app timeout"] {
+        match kv.put(&mut c, key, b"") {
+            Err(NatsError::InvalidConfig { name: "key", .. }) => {}
+            other => panic!("unexpected {:?} for {:?}", other, key),
+        }
+    }
+    config.history = 65;
+    match js.create_key_value(&mut c, &config) {
+        Err(NatsError::InvalidConfig { name: "history", ref value, .. }) => assert_eq!("65", value),
+        other => panic!("unexpected {:?}", other),
+    }
+    match js.key_value(&mut c, "my.config") {
+        Err(e @ NatsError::InvalidConfig { name: "bucket", .. }) => assert!(!e.is_retryable()),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(js.delete_key_value(&mut c, "config").unwrap());
+    server.join();
+}
+
+#[test]
+pub fn keys_history_and_watch() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let inbox_sid = conn.read_sub().1;
+        respond(conn, inbox_sid, "$JS.API.STREAM.INFO.KV_config", STREAM_INFO);
+
+        // keys
+        let sid = conn.read_sub().1;
+        let req = respond(conn, inbox_sid, "$JS.API.CONSUMER.CREATE.KV_config", &consumer_info("KV_config", "k", 3));
+        let config = req.body()["config"].clone();
+        assert_eq!("$KV.config.>", config["filter_subject"]);
+        assert_eq!("last_per_subject", config["deliver_policy"]);
+        assert_eq!(true, config["headers_only"]);
+        for (i, (key, headers)) in [("a", None), ("b", Some("NATS/1.0\r\nKV-Operation: DEL\r\n\r\n")), ("c", None)]
+            .iter()
+            .enumerate() {
+            let ack = format!("$JS.ACK.KV_config.k.1.{}.{}.1700000000000000000.{}", i + 1, i + 1, 2 - i);
+            conn.send_msg(&format!("$KV.config.{}", key), sid, Some(&ack), *headers, b"");
+        }
+        close_consumer(conn, inbox_sid, sid, "KV_config", "k");
+
+        // history of key without entries
+        let (_, sid, config) = accept_consumer(conn, inbox_sid, "KV_config", "h", 0);
+        assert_eq!("$KV.config.a", config["filter_subject"]);
+        assert_eq!("all", config["deliver_policy"]);
+        close_consumer(conn, inbox_sid, sid, "KV_config", "h");
+
+        // watch
+        let (_, sid, config) = accept_consumer(conn, inbox_sid, "KV_config", "w", 0);
+        assert_eq!("$KV.config.app.*", config["filter_subject"]);
+        assert_eq!(false, config["headers_only"]);
+        conn.send_msg("$KV.config.app.x",
+                      sid,
+                      Some("$JS.ACK.KV_config.w.1.7.1.1700000000000000000.0"),
+                      None,
+                      b"v");
+        close_consumer(conn, inbox_sid, sid, "KV_config", "w");
+    });
+    let mut c = connect(server.port);
+    let kv = JetStream::new().key_value(&mut c, "config").unwrap();
+    assert_eq!(vec!["a".to_owned(), "c".to_owned()], kv.keys(&mut c).unwrap());
+    assert!(kv.history(&mut c, "a").unwrap().is_empty());
+
+    let mut watcher = kv.watch(&mut c, "app.*").unwrap();
+    let entry = watcher.next(&mut c, Duration::from_secs(1)).unwrap().unwrap();
+    assert_eq!("app.x", entry.key);
+    assert_eq!(b"v".to_vec(), entry.value);
+    assert_eq!(7, entry.revision);
+    assert_eq!(0, entry.delta);
+    assert!(watcher.next(&mut c, Duration::from_millis(100)).unwrap().is_none());
+    watcher.stop(&mut c).unwrap();
+    server.join();
+}
+
+#[test]
+pub fn close_consumer_when_keys_fails() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let inbox_sid = conn.read_sub().1;
+        respond(conn, inbox_sid, "$JS.API.STREAM.INFO.KV_config", STREAM_INFO);
+        let (_, sid, _) = accept_consumer(conn, inbox_sid, "KV_config", "k", 2);
+        // reply subject is not JetStream ack subject
+        conn.send_msg("$KV.config.a", sid, Some("broken"), None, b"");
+        close_consumer(conn, inbox_sid, sid, "KV_config", "k");
+    });
+    let mut c = connect(server.port);
+    let kv = JetStream::new().key_value(&mut c, "config").unwrap();
+    match kv.keys(&mut c) {
+        Err(NatsError::MessageParseError(..)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    server.join();
+}
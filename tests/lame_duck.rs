@@ -0,0 +1,126 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::{connect, MockServer};
+use simple_nats_client::connect_option::ConnectOption;
+use simple_nats_client::nats_client::{ConnectionState, NatsClient, NatsError, NatsResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+pub fn migrate_from_lame_duck_server() {
+    let next = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        assert_eq!("UNSUB 0 9", conn.read_line());
+        conn.send("MSG foo 0 4\r\nnext\r\n");
+    });
+    let ldm_info = format!("INFO {{\"server_id\":\"old\",\"max_payload\":1048576,\"ldm\":true,\
+                            \"connect_urls\":[\"127.0.0.1:{}\"]}}\r\n",
+                           next.port);
+    let old = MockServer::start(common::DEFAULT_INFO, move |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        assert_eq!("UNSUB 0 10", conn.read_line());
+        conn.send("MSG foo 0 3\r\nold\r\n");
+        conn.send(&ldm_info);
+        // client unsubscribes and receives messages until PONG after moving to next server
+        assert_eq!("UNSUB 0", conn.read_line());
+        assert_eq!("PING", conn.read_line());
+        conn.send("MSG foo 0 4\r\nlate\r\nPONG\r\n");
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = connect(old.port);
+    let notified = Arc::new(Mutex::new(Vec::new()));
+    let handler_notified = notified.clone();
+    c.set_lame_duck_handler(move |info| {
+        handler_notified.lock().unwrap().push(info.server_id.clone());
+    });
+    c.set_reconnect(true);
+    let sid = c.subscribe("foo", None).unwrap();
+    c.unsubscribe_after(sid, 10).unwrap();
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"old", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    match c.wait_message() {
+        Ok(NatsResponse::Info(info)) => assert!(info.ldm),
+        _ => panic!("INFO expected"),
+    }
+    assert_eq!(vec!["old".to_owned()], *notified.lock().unwrap());
+    assert_eq!(2, c.servers().len());
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"late", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"next", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    assert!(!c.get_server_info().ldm);
+    old.join();
+    next.join();
+}
+
+#[test]
+pub fn reconnect_after_connection_lost() {
+    let next = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        conn.send("MSG foo 0 4\r\nnext\r\n");
+    });
+    let info = format!("INFO {{\"server_id\":\"old\",\"max_payload\":1048576,\
+                        \"connect_urls\":[\"127.0.0.1:{}\"]}}",
+                       next.port);
+    let old = MockServer::start(&info, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+    });
+    let mut c = connect(old.port);
+    c.set_reconnect(true);
+    c.subscribe("foo", None).unwrap();
+    old.join();
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"next", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    next.join();
+}
+
+#[test]
+pub fn reconnect_when_flush_fails() {
+    let next = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+        let req = conn.read_pub();
+        assert_eq!("foo", req.subject);
+        assert_eq!(b"after".to_vec(), req.payload);
+    });
+    let info = format!("INFO {{\"server_id\":\"old\",\"max_payload\":1048576,\
+                        \"connect_urls\":[\"127.0.0.1:{}\"]}}",
+                       next.port);
+    let old = MockServer::start(&info, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB foo 0", conn.read_line());
+    });
+    let disconnected = Arc::new(Mutex::new(Vec::new()));
+    let handler_disconnected = disconnected.clone();
+    let mut opt = ConnectOption::new();
+    opt.verbose = false;
+    opt.set_disconnected_handler(move |server, _| handler_disconnected.lock().unwrap().push(server.to_owned()));
+    let old_server = format!("127.0.0.1:{}", old.port);
+    let mut c = NatsClient::new_with_option("127.0.0.1", old.port, Some(Duration::from_secs(5)), Some(&opt)).unwrap();
+    c.set_reconnect(true);
+    c.subscribe("foo", None).unwrap();
+    old.join();
+    // publishes before flush may be lost, so flush fails even though client reconnects
+    match c.flush(Duration::from_secs(5)) {
+        Err(NatsError::ConnectionError { .. }) => {}
+        other => panic!("ConnectionError expected: {:?}", other),
+    }
+    assert_eq!(vec![old_server], *disconnected.lock().unwrap());
+    assert_eq!(ConnectionState::Connected, c.status());
+    c.publish("foo", None, b"after").unwrap();
+    next.join();
+}
@@ -0,0 +1,52 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{ConnectOption, Headers, NatsClient, NatsError, NatsResponse};
+use std::time::Duration;
+
+const SMALL_INFO: &str = r#"INFO {"server_id":"mock","version":"2.10.0","go":"go1.21","host":"127.0.0.1","port":4222,"auth_required":false,"ssl_required":false,"max_payload":16}"#;
+
+fn connect(port: i32) -> NatsClient {
+    let opt = ConnectOption::new_with_param("", "", false, "max_payload_test");
+    NatsClient::new_with_option("127.0.0.1", port, Some(Duration::from_secs(5)), Some(&opt)).unwrap()
+}
+
+#[test]
+pub fn publish_exceeds_max_payload() {
+    let server = MockServer::start(SMALL_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        // oversized messages must not reach server
+        assert_eq!("PUB small 16", conn.read_line());
+        assert_eq!(vec![1u8; 16], conn.read_payload(16));
+        conn.send(&SMALL_INFO.replace("\"max_payload\":16", "\"max_payload\":64"));
+        conn.send("\r\n");
+        assert_eq!("PUB large 32", conn.read_line());
+        assert_eq!(vec![2u8; 32], conn.read_payload(32));
+    });
+    let mut c = connect(server.port);
+    match c.publish("large", None, &[2u8; 17]) {
+        Err(NatsError::MaxPayloadExceeded { size, max }) => {
+            assert_eq!(17, size);
+            assert_eq!(16, max);
+        }
+        _ => panic!("MaxPayloadExceeded expected"),
+    }
+    let mut headers = Headers::new();
+    headers.insert("key", "value");
+    match c.publish_with_headers("large", None, &headers, &[2u8; 8]) {
+        Err(NatsError::MaxPayloadExceeded { size, .. }) => {
+            assert_eq!(headers.to_bytes().len() + 8, size)
+        }
+        _ => panic!("MaxPayloadExceeded expected"),
+    }
+    c.publish("small", None, &[1u8; 16]).unwrap();
+    match c.wait_message().unwrap() {
+        NatsResponse::Info(v) => assert_eq!(64, v.max_payload),
+        _ => panic!("INFO expected"),
+    }
+    assert_eq!(64, c.get_server_info().max_payload);
+    c.publish("large", None, &[2u8; 32]).unwrap();
+    server.join();
+}
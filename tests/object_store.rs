@@ -0,0 +1,293 @@
+extern crate base64;
+extern crate sha2;
+extern crate simple_nats_client;
+
+mod common;
+
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE as BASE64_URL};
+use base64::Engine;
+use common::{accept_consumer, close_consumer, connect, respond, MockConnection, MockServer};
+use sha2::{Digest, Sha256};
+use simple_nats_client::jetstream::object_store::{ObjectInfo, ObjectMeta, ObjectStoreConfig};
+use simple_nats_client::jetstream::JetStream;
+use simple_nats_client::nats_client::NatsError;
+use std::io::{self, ErrorKind, Read, Write};
+
+fn ack(seq: u64) -> String {
+    format!(r#"{{"stream":"OBJ_files","seq":{}}}"#, seq)
+}
+
+fn stored(subject: &str, seq: u64, data: &[u8]) -> String {
+    format!(r#"{{"message":{{"subject":"{}","seq":{},"data":"{}","time":"2024-01-01T00:00:00Z"}}}}"#,
+            subject,
+            seq,
+            BASE64.encode(data))
+}
+
+const NOT_FOUND: &str = r#"{"error":{"code":404,"err_code":10037,"description":"no message found"}}"#;
+
+const STREAM_INFO: &str = r#"{"config":{"name":"OBJ_files","subjects":["$O.files.C.>","$O.files.M.>"],
+    "retention":"limits","max_consumers":-1,"max_msgs":-1,"max_bytes":-1,"max_age":0,"max_msgs_per_subject":-1,
+    "max_msg_size":-1,"discard":"new","storage":"file","num_replicas":1,"duplicate_window":120000000000},
+    "created":"2024-01-01T00:00:00Z","state":{"messages":0,"bytes":0,"first_seq":0,"last_seq":0}}"#;
+
+/// deliver chunks to ordered consumer
+fn send_chunks(conn: &mut MockConnection, sid: u64, subject: &str, name: &str, chunks: &[&[u8]]) {
+    for (i, chunk) in chunks.iter().enumerate() {
+        let ack = format!("$JS.ACK.OBJ_files.{}.1.{}.{}.1700000000000000000.{}",
+                          name,
+                          i + 1,
+                          i + 1,
+                          chunks.len() - i - 1);
+        conn.send_msg(subject, sid, Some(&ack), None, chunk);
+    }
+}
+
+fn digest(data: &[u8]) -> String {
+    format!("SHA-256={}", BASE64_URL.encode(Sha256::digest(data)))
+}
+
+#[test]
+pub fn put_and_get_in_chunks() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let inbox_sid = conn.read_sub().1;
+        let req = respond(conn, inbox_sid, "$JS.API.STREAM.CREATE.OBJ_files", STREAM_INFO);
+        let config = req.body();
+        assert_eq!("$O.files.C.>", config["subjects"][0]);
+        assert_eq!("$O.files.M.>", config["subjects"][1]);
+        assert_eq!(true, config["allow_rollup_hdrs"]);
+
+        // put
+        let mut chunk_subjects = Vec::new();
+        for (i, expected) in [&b"hell"[..], b"o wo", b"rld"].iter().enumerate() {
+            let chunk = conn.read_pub();
+            assert_eq!(expected.to_vec(), chunk.payload);
+            conn.send_msg(chunk.reply.as_ref().unwrap(), inbox_sid, None, None, ack(i as u64 + 1).as_bytes());
+            chunk_subjects.push(chunk.subject);
+        }
+        let chunk_subject = chunk_subjects[0].clone();
+        assert!(chunk_subject.starts_with("$O.files.C."));
+        assert!(chunk_subjects.iter().all(|v| *v == chunk_subject));
+        let meta_subject = format!("$O.files.M.{}", BASE64_URL.encode("hello.txt"));
+        let req = respond(conn, inbox_sid, "$JS.API.STREAM.MSG.GET.OBJ_files", NOT_FOUND);
+        assert_eq!(meta_subject, req.body()["last_by_subj"]);
+        let meta = respond(conn, inbox_sid, &meta_subject, &ack(4));
+        assert!(meta.headers.as_ref().unwrap().contains("Nats-Rollup: sub\r\n"));
+        let info = meta.body();
+        assert_eq!("hello.txt", info["name"]);
+        assert_eq!("files", info["bucket"]);
+        assert_eq!(11, info["size"]);
+        assert_eq!(3, info["chunks"]);
+        assert_eq!(4, info["options"]["max_chunk_size"]);
+        assert_eq!(digest(b"hello world"), info["digest"]);
+        assert_eq!(format!("$O.files.C.{}", info["nuid"].as_str().unwrap()), chunk_subject);
+
+        // get
+        respond(conn,
+                inbox_sid,
+                "$JS.API.STREAM.MSG.GET.OBJ_files",
+                &stored(&meta_subject, 4, &meta.payload));
+        let (_, sid, config) = accept_consumer(conn, inbox_sid, "OBJ_files", "g", 3);
+        assert_eq!(chunk_subject, config["filter_subject"]);
+        send_chunks(conn, sid, &chunk_subject, "g", &[b"hell", b"o wo", b"rld"]);
+        close_consumer(conn, inbox_sid, sid, "OBJ_files", "g");
+
+        // get with corrupted data
+        respond(conn,
+                inbox_sid,
+                "$JS.API.STREAM.MSG.GET.OBJ_files",
+                &stored(&meta_subject, 4, &meta.payload));
+        let (_, sid, _) = accept_consumer(conn, inbox_sid, "OBJ_files", "g2", 3);
+        send_chunks(conn, sid, &chunk_subject, "g2", &[b"hell", b"o wo", b"rlD"]);
+        close_consumer(conn, inbox_sid, sid, "OBJ_files", "g2");
+
+        // link
+        let link_subject = format!("$O.files.M.{}", BASE64_URL.encode("latest"));
+        respond(conn, inbox_sid, "$JS.API.STREAM.MSG.GET.OBJ_files", NOT_FOUND);
+        let link = respond(conn, inbox_sid, &link_subject, &ack(5));
+        let link_info = link.body();
+        assert_eq!("files", link_info["options"]["link"]["bucket"]);
+        assert_eq!("hello.txt", link_info["options"]["link"]["name"]);
+
+        // delete
+        respond(conn,
+                inbox_sid,
+                "$JS.API.STREAM.MSG.GET.OBJ_files",
+                &stored(&meta_subject, 4, &meta.payload));
+        let deleted = respond(conn, inbox_sid, &meta_subject, &ack(6));
+        let deleted_info = deleted.body();
+        assert_eq!(true, deleted_info["deleted"]);
+        assert_eq!(0, deleted_info["chunks"]);
+        let req = respond(conn, inbox_sid, "$JS.API.STREAM.PURGE.OBJ_files", r#"{"success":true,"purged":3}"#);
+        assert_eq!(chunk_subject, req.body()["filter"]);
+
+        // list
+        let (_, sid, config) = accept_consumer(conn, inbox_sid, "OBJ_files", "l", 2);
+        assert_eq!("$O.files.M.>", config["filter_subject"]);
+        assert_eq!("last_per_subject", config["deliver_policy"]);
+        conn.send_msg(&link_subject,
+                      sid,
+                      Some("$JS.ACK.OBJ_files.l.1.5.1.1700000000000000000.1"),
+                      None,
+                      &link.payload);
+        conn.send_msg(&meta_subject,
+                      sid,
+                      Some("$JS.ACK.OBJ_files.l.1.6.2.1700000000000000000.0"),
+                      None,
+                      &deleted.payload);
+        close_consumer(conn, inbox_sid, sid, "OBJ_files", "l");
+    });
+    let mut c = connect(server.port);
+    let store = JetStream::new().create_object_store(&mut c, &ObjectStoreConfig::new("files")).unwrap();
+    let mut meta = ObjectMeta::new("hello.txt");
+    meta.chunk_size = 4;
+    let info = store.put(&mut c, &meta, &mut &b"hello world"[..]).unwrap();
+    assert_eq!(11, info.size);
+    assert_eq!(3, info.chunks);
+
+    let mut data = Vec::new();
+    {
+        let mut reader = store.get(&mut c, "hello.txt").unwrap().unwrap();
+        assert_eq!(info.nuid, reader.info().nuid);
+        reader.read_to_end(&mut data).unwrap();
+    }
+    assert_eq!(b"hello world".to_vec(), data);
+    {
+        let mut reader = store.get(&mut c, "hello.txt").unwrap().unwrap();
+        let e = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, e.kind());
+    }
+
+    let link = store.add_link(&mut c, "latest", &info).unwrap();
+    assert!(link.is_link());
+    assert!(store.delete(&mut c, "hello.txt").unwrap());
+
+    let objects = store.list(&mut c).unwrap();
+    assert_eq!(1, objects.len());
+    assert_eq!("latest", objects[0].name);
+    server.join();
+}
+
+#[test]
+pub fn mtime_from_stored_time() {
+    let times = [("2024-02-29T12:34:56.000000789+01:00", "2024-02-29T11:34:56.000000789Z"),
+                 ("1999-12-31T23:59:59Z", "1999-12-31T23:59:59.000000000Z"),
+                 ("2100-03-01T00:00:00.5Z", "2100-03-01T00:00:00.500000000Z")];
+    let server = MockServer::start(common::DEFAULT_INFO, move |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let inbox_sid = conn.read_sub().1;
+        respond(conn, inbox_sid, "$JS.API.STREAM.INFO.OBJ_files", STREAM_INFO);
+        let meta_subject = format!("$O.files.M.{}", BASE64_URL.encode("old.txt"));
+        // metadata without mtime
+        let meta = br#"{"name":"old.txt","bucket":"files","nuid":"n","size":0,"mtime":"","chunks":0}"#;
+        for &(time, _) in &times {
+            let stored = format!(r#"{{"message":{{"subject":"{}","seq":1,"data":"{}","time":"{}"}}}}"#,
+                                 meta_subject,
+                                 BASE64.encode(&meta[..]),
+                                 time);
+            respond(conn, inbox_sid, "$JS.API.STREAM.MSG.GET.OBJ_files", &stored);
+        }
+    });
+    let mut c = connect(server.port);
+    let store = JetStream::new().object_store(&mut c, "files").unwrap();
+    for &(_, mtime) in &times {
+        assert_eq!(mtime, store.info(&mut c, "old.txt").unwrap().unwrap().mtime);
+    }
+    server.join();
+}
+
+struct BrokenReader;
+
+impl Read for BrokenReader {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("broken"))
+    }
+}
+
+#[test]
+pub fn remove_chunks_of_unfinished_object() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let inbox_sid = conn.read_sub().1;
+        respond(conn, inbox_sid, "$JS.API.STREAM.INFO.OBJ_files", STREAM_INFO);
+
+        // put with broken reader
+        let req = respond(conn, inbox_sid, "$JS.API.STREAM.PURGE.OBJ_files", r#"{"success":true,"purged":0}"#);
+        assert!(req.body()["filter"].as_str().unwrap().starts_with("$O.files.C."));
+
+        // writer dropped without finish
+        let chunk = conn.read_pub();
+        assert_eq!(b"abcd".to_vec(), chunk.payload);
+        conn.send_msg(chunk.reply.as_ref().unwrap(), inbox_sid, None, None, ack(1).as_bytes());
+        let req = respond(conn, inbox_sid, "$JS.API.STREAM.PURGE.OBJ_files", r#"{"success":true,"purged":1}"#);
+        assert_eq!(chunk.subject, req.body()["filter"]);
+
+        // link to name of object
+        let meta = br#"{"name":"hello.txt","bucket":"files","nuid":"n","size":0,"mtime":"","chunks":0}"#;
+        respond(conn,
+                inbox_sid,
+                "$JS.API.STREAM.MSG.GET.OBJ_files",
+                &stored(&format!("$O.files.M.{}", BASE64_URL.encode("hello.txt")), 1, meta));
+    });
+    let mut c = connect(server.port);
+    let store = JetStream::new().object_store(&mut c, "files").unwrap();
+    match store.put(&mut c, &ObjectMeta::new("broken.txt"), &mut BrokenReader) {
+        Err(e @ NatsError::Io { .. }) => assert!(!e.is_retryable()),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let mut meta = ObjectMeta::new("dropped.txt");
+    meta.chunk_size = 4;
+    {
+        let mut writer = store.writer(&mut c, &meta).unwrap();
+        writer.write_all(b"abcdef").unwrap();
+    }
+
+    let target = ObjectInfo {
+        name: "old.txt".to_owned(),
+        bucket: "files".to_owned(),
+        deleted: true,
+        ..Default::default()
+    };
+    match store.add_link(&mut c, "latest", &target) {
+        Err(NatsError::InvalidLink { ref name, .. }) => assert_eq!("old.txt", name),
+        other => panic!("unexpected {:?}", other),
+    }
+    let target = ObjectInfo {
+        deleted: false,
+        ..target
+    };
+    match store.add_link(&mut c, "hello.txt", &target) {
+        Err(NatsError::ObjectExists(ref name)) => assert_eq!("hello.txt", name),
+        other => panic!("unexpected {:?}", other),
+    }
+    server.join();
+}
+
+#[test]
+pub fn close_consumer_of_dropped_reader() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let inbox_sid = conn.read_sub().1;
+        respond(conn, inbox_sid, "$JS.API.STREAM.INFO.OBJ_files", STREAM_INFO);
+        let meta = br#"{"name":"big.txt","bucket":"files","nuid":"n","size":8,"mtime":"","chunks":2}"#;
+        respond(conn,
+                inbox_sid,
+                "$JS.API.STREAM.MSG.GET.OBJ_files",
+                &stored(&format!("$O.files.M.{}", BASE64_URL.encode("big.txt")), 1, meta));
+        let (_, sid, _) = accept_consumer(conn, inbox_sid, "OBJ_files", "d", 2);
+        send_chunks(conn, sid, "$O.files.C.n", "d", &[b"abcd"]);
+        // reader is dropped before second chunk
+        close_consumer(conn, inbox_sid, sid, "OBJ_files", "d");
+    });
+    let mut c = connect(server.port);
+    let store = JetStream::new().object_store(&mut c, "files").unwrap();
+    {
+        let mut reader = store.get(&mut c, "big.txt").unwrap().unwrap();
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(b"abcd", &buf);
+    }
+    server.join();
+}
@@ -0,0 +1,147 @@
+extern crate simple_nats_client;
+extern crate serde_json;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{ConnectionState, NatsError};
+use simple_nats_client::options::Options;
+use std::net::TcpListener;
+use std::time::Duration;
+
+const PROTO1_INFO: &str = r#"INFO {"server_id":"mock","version":"2.10.0","proto":1,"max_payload":1048576}"#;
+
+fn unused_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+#[test]
+pub fn connect_with_builder() {
+    let server = MockServer::start(PROTO1_INFO, |conn| {
+        let line = conn.read_line();
+        let connect: serde_json::Value = serde_json::from_str(&line["CONNECT ".len()..]).unwrap();
+        assert_eq!("app", connect["name"]);
+        assert_eq!("u", connect["user"]);
+        assert_eq!("p", connect["pass"]);
+        assert_eq!(false, connect["echo"]);
+        assert_eq!(false, connect["verbose"]);
+    });
+    let c = Options::new()
+        .servers(&[unused_server(), format!("127.0.0.1:{}", server.port)])
+        .name("app")
+        .user_and_password("u", "p")
+        .echo(false)
+        .read_timeout(Duration::from_secs(5))
+        .connect()
+        .unwrap();
+    assert_eq!(ConnectionState::Connected, c.status());
+    assert_eq!(2, c.servers().len());
+    server.join();
+}
+
+#[test]
+pub fn stale_connection_by_ping_interval() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("PING", conn.read_line());
+        conn.send("PONG\r\n");
+        // second PING is not answered
+        assert_eq!("PING", conn.read_line());
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = Options::new()
+        .server(&format!("127.0.0.1:{}", server.port))
+        .read_timeout(Duration::from_secs(5))
+        .ping_interval(Duration::from_millis(50))
+        .max_pings_out(1)
+        .connect()
+        .unwrap();
+    match c.wait_message() {
+        Err(NatsError::ConnectionError { .. }) => {}
+        _ => panic!("ConnectionError expected"),
+    }
+    assert_eq!(ConnectionState::Closed, c.status());
+    drop(c);
+    server.join();
+}
+
+#[test]
+pub fn tls_is_not_supported() {
+    match Options::new().server(&unused_server()).tls_required(true).connect() {
+        Err(NatsError::ConnectionError { .. }) => {}
+        _ => panic!("ConnectionError expected"),
+    }
+}
+
+#[test]
+pub fn report_attempted_addresses() {
+    let server = unused_server();
+    let port = server.rsplit(':').next().unwrap().to_owned();
+    match Options::new().server(&format!("localhost:{}", port)).connect_timeout(Duration::from_secs(1)).connect() {
+        Err(e @ NatsError::ConnectFailed { .. }) => {
+            if let NatsError::ConnectFailed { ref attempts, .. } = e {
+                assert!(!attempts.is_empty());
+                for &(addr, _) in attempts {
+                    assert_eq!(port, addr.port().to_string());
+                    assert!(e.to_string().contains(&addr.to_string()));
+                }
+            }
+            assert!(e.is_retryable());
+        }
+        _ => panic!("ConnectFailed expected"),
+    }
+}
+
+#[test]
+pub fn connect_timeout_waiting_info() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let server = listener.local_addr().unwrap().to_string();
+    // connection is accepted by backlog, but INFO is never sent
+    match Options::new().server(&server).connect_timeout(Duration::from_millis(100)).connect() {
+        Err(NatsError::Timeout { .. }) => {}
+        _ => panic!("Timeout expected"),
+    }
+    drop(listener);
+}
+
+#[test]
+pub fn zero_max_reconnects_disables_reconnect() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+    });
+    let mut c = Options::new()
+        .server(&format!("127.0.0.1:{}", server.port))
+        .read_timeout(Duration::from_secs(5))
+        .max_reconnects(0)
+        .reconnect_wait(Duration::from_millis(10))
+        .connect()
+        .unwrap();
+    server.join();
+    match c.wait_message() {
+        Err(NatsError::ConnectionError { .. }) => {}
+        _ => panic!("ConnectionError expected"),
+    }
+    assert_eq!(ConnectionState::Closed, c.status());
+}
+
+#[test]
+pub fn try_next_server_when_connect_fails() {
+    // first server closes connection without answering CONNECT
+    let first = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+    });
+    let second = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        conn.send("+OK\r\n");
+    });
+    let c = Options::new()
+        .servers(&[format!("127.0.0.1:{}", first.port), format!("127.0.0.1:{}", second.port)])
+        .read_timeout(Duration::from_secs(5))
+        .verbose(true)
+        .connect()
+        .unwrap();
+    assert_eq!(ConnectionState::Connected, c.status());
+    first.join();
+    second.join();
+}
@@ -0,0 +1,70 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{NatsClient, NatsError, NatsMessage};
+use simple_nats_client::protocol::{Parser, ServerOp};
+use std::time::Duration;
+
+fn parse_one(data: &[u8]) -> Result<Option<ServerOp>, NatsError> {
+    let mut parser = Parser::new();
+    parser.feed(data);
+    parser.next_op()
+}
+
+#[test]
+pub fn malformed_input_returns_error() {
+    let inputs: &[&[u8]] = &[b"INFO {\"server_id\":\r\n",
+                             b"INFO\r\n",
+                             b"MSG\r\n",
+                             b"MSG foo\r\n",
+                             b"MSG foo 1 -3\r\n",
+                             b"MSG foo x 3\r\n",
+                             b"MSG foo 1 99999999999999999999\r\n",
+                             b"HMSG foo 1 10 5\r\n",
+                             b"MSG foo 1 3\r\nabcde\r\n",
+                             b"HMSG foo 1 5 5\r\nXXXXX\r\n",
+                             b"\xff\xfe\r\n",
+                             b"FOO bar\r\n"];
+    for input in inputs {
+        assert!(parse_one(input).is_err(), "{:?} should be error", input);
+    }
+}
+
+#[test]
+pub fn parse_split_message() {
+    let data = b"PING\r\nMSG foo.bar 3 _INBOX.x 5\r\nhello\r\n+OK\r\n";
+    // feed byte by byte to check every chunk boundary
+    let mut parser = Parser::new();
+    let mut ops = Vec::new();
+    for b in data.iter() {
+        parser.feed(&[*b]);
+        while let Some(op) = parser.next_op().unwrap() {
+            ops.push(op);
+        }
+    }
+    assert_eq!(3, ops.len());
+    match ops[1] {
+        ServerOp::Msg(ref m) => {
+            assert_eq!("foo.bar", m.subject);
+            assert_eq!(3, m.sid);
+            assert_eq!(Some("_INBOX.x".to_owned()), m.reply);
+            assert_eq!(b"hello".to_vec(), m.data);
+            assert_eq!(NatsMessage::new(3, "foo.bar", Some("_INBOX.x"), b"hello".to_vec()), *m);
+        }
+        _ => panic!("MSG expected"),
+    }
+    assert_eq!(0, parser.buffered_len());
+}
+
+#[test]
+pub fn malformed_info_on_connect() {
+    let server = MockServer::start("INFO {\"server_id\":", |_| {});
+    match NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_secs(5)), None) {
+        Err(NatsError::MessageParseError(..)) => {}
+        Err(e) => panic!("parse error expected:{}", e),
+        Ok(_) => panic!("parse error expected"),
+    }
+    server.join();
+}
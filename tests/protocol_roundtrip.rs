@@ -0,0 +1,144 @@
+// property tests of wire protocol encoding/parsing, they do not require NATS server
+extern crate simple_nats_client;
+extern crate proptest;
+
+use proptest::prelude::*;
+use simple_nats_client::nats_client::Headers;
+use simple_nats_client::protocol::{ClientOp, Parser, ServerOp};
+
+fn subject() -> impl Strategy<Value = String> {
+    prop::collection::vec("[a-zA-Z0-9_$-]{1,8}", 1..5).prop_map(|v| v.join("."))
+}
+
+fn headers() -> impl Strategy<Value = Headers> {
+    // values are trimmed by parser, so they must not have surrounding spaces
+    prop::collection::vec(("[A-Za-z][A-Za-z0-9-]{0,15}", "([!-~][ -~]{0,20})?[!-~]?"), 0..5)
+        .prop_map(|v| {
+            let mut h = Headers::new();
+            for (name, value) in v {
+                h.append(&name, value.trim());
+            }
+            h
+        })
+}
+
+fn client_op() -> impl Strategy<Value = ClientOp> {
+    let payload = prop::collection::vec(any::<u8>(), 0..256);
+    prop_oneof![
+        (subject(), prop::option::of(subject()), payload.clone())
+            .prop_map(|(subject, reply, payload)| ClientOp::Pub { subject, reply, payload }),
+        (subject(), prop::option::of(subject()), headers(), payload).prop_map(
+            |(subject, reply, headers, payload)| ClientOp::Hpub { subject, reply, headers, payload }
+        ),
+        (subject(), prop::option::of("[a-z]{1,8}"), any::<u64>())
+            .prop_map(|(subject, queue, sid)| ClientOp::Sub { subject, queue, sid }),
+        (any::<u64>(), prop::option::of(any::<u64>()))
+            .prop_map(|(sid, max)| ClientOp::Unsub { sid, max }),
+        Just(ClientOp::Ping),
+        Just(ClientOp::Pong),
+    ]
+}
+
+/// server side encoding of MSG/HMSG
+fn encode_msg(subject: &str,
+              sid: u64,
+              reply: &Option<String>,
+              headers: &Option<Headers>,
+              payload: &[u8])
+              -> Vec<u8> {
+    let reply = reply.as_ref().map(|v| format!(" {}", v)).unwrap_or_default();
+    let mut ret = Vec::new();
+    match *headers {
+        Some(ref h) => {
+            let hdr = h.to_bytes();
+            let line = format!("HMSG {} {}{} {} {}\r\n",
+                               subject,
+                               sid,
+                               reply,
+                               hdr.len(),
+                               hdr.len() + payload.len());
+            ret.extend_from_slice(line.as_bytes());
+            ret.extend_from_slice(&hdr);
+        }
+        None => {
+            let line = format!("MSG {} {}{} {}\r\n", subject, sid, reply, payload.len());
+            ret.extend_from_slice(line.as_bytes());
+        }
+    }
+    ret.extend_from_slice(payload);
+    ret.extend_from_slice(b"\r\n");
+    ret
+}
+
+proptest! {
+    #[test]
+    fn client_op_roundtrip(ops in prop::collection::vec(client_op(), 1..8)) {
+        let mut data = Vec::new();
+        for op in &ops {
+            data.extend_from_slice(&op.encode());
+        }
+        let mut decoded = Vec::new();
+        let mut offset = 0;
+        while let Some((op, consumed)) = ClientOp::decode(&data[offset..]).unwrap() {
+            decoded.push(op);
+            offset += consumed;
+        }
+        prop_assert_eq!(data.len(), offset);
+        prop_assert_eq!(ops, decoded);
+    }
+
+    #[test]
+    fn message_with_arbitrary_chunks(subject in subject(),
+                                     sid in any::<u64>(),
+                                     reply in prop::option::of(subject()),
+                                     headers in prop::option::of(headers()),
+                                     payload in prop::collection::vec(any::<u8>(), 0..512),
+                                     chunks in prop::collection::vec(1usize..64, 1..32)) {
+        let mut data = b"PING\r\n".to_vec();
+        data.extend_from_slice(&encode_msg(&subject, sid, &reply, &headers, &payload));
+        data.extend_from_slice(b"-ERR 'Stale Connection'\r\n");
+        let mut parser = Parser::new();
+        let mut ops = Vec::new();
+        let mut offset = 0;
+        for size in chunks.iter().cycle() {
+            if offset >= data.len() {
+                break;
+            }
+            let end = ::std::cmp::min(offset + size, data.len());
+            parser.feed(&data[offset..end]);
+            offset = end;
+            while let Some(op) = parser.next_op().unwrap() {
+                ops.push(op);
+            }
+        }
+        prop_assert_eq!(3, ops.len());
+        match ops[1] {
+            ServerOp::Msg(ref m) => {
+                prop_assert_eq!(&subject, &m.subject);
+                prop_assert_eq!(sid, m.sid);
+                prop_assert_eq!(&reply, &m.reply);
+                prop_assert_eq!(&headers, &m.headers);
+                prop_assert_eq!(&payload, &m.data);
+            }
+            ref op => prop_assert!(false, "MSG expected:{:?}", op),
+        }
+        match ops[2] {
+            ServerOp::Err(ref e) => {
+                prop_assert_eq!("'Stale Connection'", e.error_message.as_str())
+            }
+            ref op => prop_assert!(false, "-ERR expected:{:?}", op),
+        }
+    }
+
+    #[test]
+    fn parser_never_panics(data in prop::collection::vec(any::<u8>(), 0..1024)) {
+        let mut parser = Parser::new();
+        parser.feed(&data);
+        for _ in 0..data.len() + 1 {
+            match parser.next_op() {
+                Ok(Some(_)) | Err(_) => {}
+                Ok(None) => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate serde_derive;
+extern crate simple_nats_client;
+
+mod common;
+
+use common::{connect, MockServer};
+use simple_nats_client::nats_client::{NatsError, NatsResponse};
+use std::time::Duration;
+
+#[test]
+pub fn request_reply() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB other 0", conn.read_line());
+        let (inbox, sid) = conn.read_sub();
+        assert!(inbox.starts_with("_INBOX.") && inbox.ends_with(".*"));
+        let req = conn.read_pub();
+        assert_eq!("service", req.subject);
+        assert_eq!(b"ping".to_vec(), req.payload);
+        let reply = req.reply.unwrap();
+        assert!(reply.starts_with(&inbox[..inbox.len() - 1]));
+        // message of another subscription arrives before the reply
+        conn.send_msg("other", 0, None, None, b"other");
+        conn.send_msg(&reply, sid, None, None, b"pong");
+        let req = conn.read_pub();
+        conn.send_msg(&req.reply.unwrap(), sid, None, Some("NATS/1.0 503\r\n\r\n"), b"");
+    });
+    let mut c = connect(server.port);
+    c.subscribe("other", None).unwrap();
+    let msg = c.request("service", b"ping", Duration::from_secs(5)).unwrap();
+    assert_eq!(b"pong", &msg.data[..]);
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"other", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    match c.request("nobody", b"", Duration::from_secs(5)) {
+        Err(NatsError::NoResponders(ref subject)) => assert_eq!("nobody", subject),
+        _ => panic!("NoResponders expected"),
+    }
+    server.join();
+}
+
+#[test]
+pub fn request_timeout() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        conn.read_sub();
+        conn.read_pub();
+        assert_eq!("", conn.read_line());
+    });
+    let mut c = connect(server.port);
+    match c.request("service", b"", Duration::from_millis(100)) {
+        Err(NatsError::Timeout { .. }) => {}
+        _ => panic!("Timeout expected"),
+    }
+    drop(c);
+    server.join();
+}
+
+#[test]
+pub fn discard_late_reply() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        assert_eq!("SUB other 0", conn.read_line());
+        let (_, sid) = conn.read_sub();
+        let req = conn.read_pub();
+        // reply is sent after client gives up
+        assert_eq!("done", conn.read_pub().subject);
+        conn.send_msg(&req.reply.unwrap(), sid, None, None, b"late");
+        conn.send_msg("other", 0, None, None, b"other");
+    });
+    let mut c = connect(server.port);
+    c.subscribe("other", None).unwrap();
+    match c.request("service", b"", Duration::from_millis(100)) {
+        Err(NatsError::Timeout { .. }) => {}
+        _ => panic!("Timeout expected"),
+    }
+    c.publish("done", None, b"").unwrap();
+    match c.wait_message() {
+        Ok(NatsResponse::Msg(msg)) => assert_eq!(b"other", &msg.data[..]),
+        _ => panic!("MSG expected"),
+    }
+    server.join();
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Order {
+    id: u64,
+    item: String,
+}
+
+#[test]
+pub fn publish_and_request_json() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        let req = conn.read_pub();
+        assert_eq!("orders", req.subject);
+        assert_eq!(br#"{"id":1,"item":"book"}"#.to_vec(), req.payload);
+        let sid = conn.read_sub().1;
+        let req = conn.read_pub();
+        assert_eq!(br#"{"id":2,"item":"pen"}"#.to_vec(), req.payload);
+        conn.send_msg(&req.reply.unwrap(), sid, None, None, br#"{"id":2,"item":"pen (reserved)"}"#);
+        let req = conn.read_pub();
+        conn.send_msg(&req.reply.unwrap(), sid, None, None, b"not json");
+    });
+    let mut c = connect(server.port);
+    c.publish_json("orders", None, &Order { id: 1, item: "book".to_owned() }).unwrap();
+    let reply: Order = c.request_json("orders.reserve",
+                                      &Order { id: 2, item: "pen".to_owned() },
+                                      Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(Order { id: 2, item: "pen (reserved)".to_owned() }, reply);
+    match c.request_json::<_, Order>("orders.reserve", &Order { id: 3, item: "ink".to_owned() }, Duration::from_secs(5)) {
+        Err(NatsError::JsonError(e)) => assert!(e.is_syntax()),
+        other => panic!("JsonError expected: {:?}", other),
+    }
+    server.join();
+}
@@ -0,0 +1,71 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{NatsClient, NatsError, NatsResponse, PermissionViolation};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+pub fn parse_permission_violation() {
+    assert_eq!(Some(PermissionViolation::Subscription {
+                   subject: "foo.bar".to_owned(),
+                   queue: None,
+               }),
+               PermissionViolation::parse("'Permissions Violation for Subscription to \"foo.bar\"'"));
+    assert_eq!(Some(PermissionViolation::Subscription {
+                   subject: "foo".to_owned(),
+                   queue: Some("q".to_owned()),
+               }),
+               PermissionViolation::parse("'Permissions Violation for Subscription to \"foo\" using queue \"q\"'"));
+    assert_eq!(Some(PermissionViolation::Publish { subject: "foo".to_owned() }),
+               PermissionViolation::parse("'Permissions Violation for Publish to \"foo\"'"));
+    assert_eq!(Some(PermissionViolation::PublishReply { subject: "_INBOX.x".to_owned() }),
+               PermissionViolation::parse("'Permissions Violation for Publish with Reply of \"_INBOX.x\"'"));
+    assert_eq!(None, PermissionViolation::parse("'Unknown Protocol Operation'"));
+}
+
+#[test]
+pub fn subscription_permission_violation() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        // client without ConnectOption expects verbose responses
+        assert_eq!("SUB allowed 0", conn.read_line());
+        conn.send("+OK\r\n");
+        assert_eq!("SUB denied 1", conn.read_line());
+        conn.send("+OK\r\n");
+        conn.send("-ERR 'Permissions Violation for Subscription to \"denied\"'\r\n");
+        conn.send("MSG allowed 0 2\r\nok\r\n");
+    });
+    let mut c = NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_secs(5)), None)
+        .unwrap();
+    let notified = Arc::new(Mutex::new(Vec::new()));
+    let handler_notified = notified.clone();
+    c.set_async_error_handler(move |_, sids| {
+        handler_notified.lock().unwrap().extend_from_slice(sids);
+    });
+    let allowed = c.subscribe("allowed", None).unwrap();
+    let denied = c.subscribe("denied", None).unwrap();
+    for _ in 0..2 {
+        match c.wait_message() {
+            Ok(NatsResponse::Ok) => {}
+            _ => panic!("+OK expected"),
+        }
+    }
+    match c.wait_message() {
+        Err(NatsError::ServerError(e)) => {
+            assert_eq!(Some(PermissionViolation::Subscription {
+                           subject: "denied".to_owned(),
+                           queue: None,
+                       }),
+                       e.permission_violation)
+        }
+        _ => panic!("permission violation expected"),
+    }
+    assert_eq!(vec![denied], *notified.lock().unwrap());
+    assert!(c.subscription_error(denied).is_some());
+    assert!(c.subscription_error(allowed).is_none());
+    // connection is still alive after the error
+    assert!(c.wait_message().is_ok());
+    server.join();
+}
@@ -0,0 +1,56 @@
+extern crate simple_nats_client;
+extern crate serde_json;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::connect_option::ConnectOption;
+use simple_nats_client::nats_client::{NatsClient, NatsResponse};
+use simple_nats_client::server_info::ServerInfo;
+use std::time::Duration;
+
+#[test]
+pub fn parse_full_server_info() {
+    let json = r#"{"server_id":"NAB","server_name":"n1","version":"2.10.0","proto":1,"go":"go1.21",
+        "host":"0.0.0.0","port":4222,"headers":true,"auth_required":true,"tls_required":true,
+        "tls_verify":true,"max_payload":1048576,"client_id":42,"client_ip":"10.0.0.1",
+        "nonce":"abc","cluster":"c1","domain":"hub","connect_urls":["10.0.0.2:4222"],
+        "ldm":true,"jetstream":true,"xkey":"XKEY"}"#;
+    let info: ServerInfo = serde_json::from_str(json).unwrap();
+    assert_eq!(4222u16, info.port);
+    assert_eq!(1, info.proto);
+    assert_eq!(42, info.client_id);
+    assert_eq!("10.0.0.1", info.client_ip);
+    assert!(info.headers && info.tls_required && info.tls_verify && info.ldm && info.jetstream);
+    assert_eq!(Some("abc".to_owned()), info.nonce);
+    assert_eq!(Some("c1".to_owned()), info.cluster);
+    assert_eq!(Some("hub".to_owned()), info.domain);
+    assert_eq!(vec!["10.0.0.2:4222".to_owned()], info.connect_urls);
+    // unknown fields are preserved and serialized again
+    assert_eq!(Some(&serde_json::Value::from("XKEY")), info.extra.get("xkey"));
+    let again: ServerInfo = serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+    assert_eq!(info, again);
+    // fields missing in INFO have default value
+    let minimal: ServerInfo = serde_json::from_str(r#"{"server_id":"x"}"#).unwrap();
+    assert_eq!(0, minimal.proto);
+    assert!(minimal.connect_urls.is_empty());
+}
+
+#[test]
+pub fn async_info_updates_server_info() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        conn.send("INFO {\"server_id\":\"mock\",\"port\":4222,\"max_payload\":2048,\"connect_urls\":[\"127.0.0.1:4223\"]}\r\n");
+    });
+    let mut opt = ConnectOption::new();
+    opt.verbose = false;
+    let mut c = NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_secs(5)), Some(&opt))
+        .unwrap();
+    assert!(c.get_server_info().connect_urls.is_empty());
+    match c.wait_message() {
+        Ok(NatsResponse::Info(_)) => {}
+        _ => panic!("INFO expected"),
+    }
+    assert_eq!(2048, c.get_server_info().max_payload);
+    assert_eq!(vec!["127.0.0.1:4223".to_owned()], c.get_server_info().connect_urls);
+    server.join();
+}
@@ -0,0 +1,108 @@
+extern crate simple_nats_client;
+
+mod common;
+
+use common::MockServer;
+use simple_nats_client::nats_client::{ConnectOption, NatsClient, NatsError};
+use simple_nats_client::subject;
+use std::time::Duration;
+
+#[test]
+pub fn validate_subjects() {
+    for s in &["foo", "foo.bar", "foo.*.baz", "foo.>", ">", "*", "foo*.bar", "_INBOX.abc"] {
+        assert!(subject::validate_subject(s).is_ok(), "{} should be valid", s);
+    }
+    for s in &["", "foo bar", "foo\tbar", "foo\r\nPUB x 1", ".foo", "foo.", "foo..bar", "foo.>.bar"] {
+        assert!(subject::validate_subject(s).is_err(), "{:?} should be invalid", s);
+    }
+    assert!(subject::validate_publish_subject("foo.bar").is_ok());
+    assert!(subject::validate_publish_subject("foo*.bar").is_ok());
+    assert!(subject::validate_publish_subject("foo.*").is_err());
+    assert!(subject::validate_publish_subject(">").is_err());
+    assert!(subject::validate_queue_name("workers.a").is_ok());
+    assert!(subject::validate_queue_name("").is_err());
+    assert!(subject::validate_queue_name("q 1").is_err());
+}
+
+#[test]
+pub fn reject_invalid_subject_before_sending() {
+    let server = MockServer::start(common::DEFAULT_INFO, |conn| {
+        assert!(conn.read_line().starts_with("CONNECT "));
+        // nothing is written for invalid requests
+        assert_eq!("SUB foo.* 0", conn.read_line());
+        assert_eq!("PUB foo.bar 0", conn.read_line());
+    });
+    let opt = ConnectOption::new_with_param("", "", false, "subject_test");
+    let mut c = NatsClient::new_with_option("127.0.0.1", server.port, Some(Duration::from_secs(5)), Some(&opt))
+        .unwrap();
+    match c.publish("foo.*", None, &[]) {
+        Err(NatsError::InvalidSubject { subject, .. }) => assert_eq!("foo.*", subject),
+        _ => panic!("InvalidSubject expected"),
+    }
+    match c.publish("foo", Some("bar 1\r\nPUB baz"), &[]) {
+        Err(NatsError::InvalidSubject { .. }) => {}
+        _ => panic!("InvalidSubject expected"),
+    }
+    match c.subscribe("foo bar", None) {
+        Err(NatsError::InvalidSubject { .. }) => {}
+        _ => panic!("InvalidSubject expected"),
+    }
+    match c.subscribe("foo", Some("")) {
+        Err(NatsError::InvalidSubject { .. }) => {}
+        _ => panic!("InvalidSubject expected"),
+    }
+    c.subscribe("foo.*", None).unwrap();
+    c.publish("foo.bar", None, &[]).unwrap();
+    server.join();
+}
+
+#[test]
+pub fn match_and_capture() {
+    assert!(subject::matches("foo.bar", "foo.bar"));
+    assert!(subject::matches("foo.*", "foo.bar"));
+    assert!(!subject::matches("foo.*", "foo.bar.baz"));
+    assert!(subject::matches("foo.>", "foo.bar.baz"));
+    assert!(subject::matches(">", "foo"));
+    assert!(!subject::matches("foo.>", "foo"));
+    assert!(!subject::matches("foo.bar.baz", "foo.bar"));
+    assert_eq!(Some(vec!["1", "2"]), subject::captures("a.*.b.*", "a.1.b.2"));
+    assert_eq!(Some(vec!["x", "y.z"]), subject::captures("a.*.>", "a.x.y.z"));
+    assert_eq!(Some(vec![]), subject::captures("a.b", "a.b"));
+}
+
+#[test]
+pub fn subject_map() {
+    let mut m = subject::SubjectMap::new();
+    assert_eq!(None, m.insert("a.b.c", "exact").unwrap());
+    assert_eq!(None, m.insert("a.*.c", "single").unwrap());
+    assert_eq!(None, m.insert("a.>", "full").unwrap());
+    assert_eq!(Some("full"), m.insert("a.>", "tail").unwrap());
+    assert!(m.insert("a.>.c", "invalid").is_err());
+    assert_eq!(3, m.len());
+    let mut found = m.matches("a.b.c");
+    found.sort();
+    assert_eq!(vec![&"exact", &"single", &"tail"], found);
+    assert_eq!(vec![&"tail"], m.matches("a.b"));
+    assert!(m.matches("a").is_empty());
+    assert_eq!(Some(&"single"), m.get("a.*.c"));
+    assert_eq!(Some("single"), m.remove("a.*.c"));
+    assert_eq!(None, m.remove("a.*.c"));
+    assert_eq!(2, m.len());
+    let mut found = m.matches("a.x.c");
+    found.sort();
+    assert_eq!(vec![&"tail"], found);
+}
+
+#[test]
+pub fn subject_map_literal_wildcard_token() {
+    let mut m = subject::SubjectMap::new();
+    m.insert("a.*", "single").unwrap();
+    m.insert("a.>", "full").unwrap();
+    // wildcard token of subject matches its own pattern only once
+    let mut found = m.matches("a.*");
+    found.sort();
+    assert_eq!(vec![&"full", &"single"], found);
+    let mut found = m.matches("a.>");
+    found.sort();
+    assert_eq!(vec![&"full", &"single"], found);
+}